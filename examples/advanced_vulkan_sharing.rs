@@ -21,7 +21,7 @@
 use bevy::prelude::*;
 use bevy::window::WindowPlugin;
 use bevy::winit::WinitPlugin;
-use bevy_external_surface::vulkan_sharing::{VulkanSharingPlugin, VulkanSharingConfig, SharedVulkanResources};
+use bevy_external_surface::vulkan_sharing::{BufferSwapPolicy, ColorSpace, FrameSyncMode, MemoryExportMode, SharedSurfaceId, SharedSurfaceTarget, SharedSurfaces, VulkanSharingPlugin, VulkanSharingConfig};
 use ash::vk;
 use std::time::{Duration, Instant};
 
@@ -46,12 +46,18 @@ fn main() {
         )
         // Advanced Vulkan sharing with all features enabled
         .add_plugins(VulkanSharingPlugin {
+            id: SharedSurfaceId::default(),
             config: VulkanSharingConfig {
                 width: 1920,
                 height: 1080,
                 format: vk::Format::B8G8R8A8_SRGB,
                 ipc_socket_path: Some("/tmp/advanced_vulkan_sharing.sock".to_string()),
-                enable_double_buffering: true,  // Enable for smooth playback
+                buffer_count: 3,  // Triple buffering for smooth playback
+                swap_policy: BufferSwapPolicy::Mailbox,  // Slow consumers only see the newest frames
+                sync_mode: FrameSyncMode::Timeline,  // One semaphore per direction, exported once
+                memory_export: MemoryExportMode::OpaqueFd,  // DmaBuf for non-Vulkan consumers
+                color_space: ColorSpace::Srgb,  // ScRgb with R16G16B16A16_SFLOAT for HDR consumers
+                share_depth: false,  // Also share depth, with Msaa::Off on the camera
             },
         })
         .insert_resource(PerformanceStats::default())
@@ -119,6 +125,7 @@ fn setup_advanced_scene(
     // Advanced camera with better positioning
     commands.spawn((
         Camera3d::default(),
+        SharedSurfaceTarget::default(),
         Transform::from_xyz(10.0, 8.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
         MainCamera,
     ));
@@ -256,6 +263,7 @@ struct DynamicLight {
     intensity_variation: f32,
 }
 
+#[allow(clippy::type_complexity)]
 fn animate_advanced_scene(
     time: Res<Time>,
     scene_config: Res<SceneConfig>,
@@ -334,9 +342,11 @@ fn update_performance_stats(
 fn handle_controls(
     keys: Res<ButtonInput<KeyCode>>,
     mut stats: ResMut<PerformanceStats>,
-    mut shared_resources: ResMut<SharedVulkanResources>,
+    mut shared_surfaces: ResMut<SharedSurfaces>,
 ) {
-    if keys.just_pressed(KeyCode::Space) {
+    if keys.just_pressed(KeyCode::Space)
+        && let Some(shared_resources) = shared_surfaces.get_mut(SharedSurfaceId::default())
+    {
         info!("Manual buffer swap requested");
         shared_resources.swap_buffers();
         stats.buffer_swaps += 1;
@@ -354,8 +364,12 @@ fn handle_controls(
 }
 
 fn manage_synchronization(
-    shared_resources: Res<SharedVulkanResources>,
+    shared_surfaces: Res<SharedSurfaces>,
 ) {
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
     // In a real application, this is where you would:
     // 1. Wait for consumer-ready semaphores before rendering to a buffer
     // 2. Signal render-finished semaphores after GPU work completes
@@ -363,17 +377,21 @@ fn manage_synchronization(
     // 4. Adjust rendering quality based on consumer performance
     
     // For now, we'll just track that synchronization is being managed
-    if shared_resources.render_finished_semaphores.len() != shared_resources.consumer_ready_semaphores.len() {
-        warn!("Semaphore count mismatch - synchronization may be unstable");
+    if !shared_resources.is_ready() {
+        warn!("Shared textures or semaphores missing - synchronization may be unstable");
     }
 }
 
 fn log_advanced_status(
     time: Res<Time>,
     stats: Res<PerformanceStats>,
-    shared_resources: Res<SharedVulkanResources>,
+    shared_surfaces: Res<SharedSurfaces>,
     mut last_log_time: Local<f32>,
 ) {
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
     let current_time = time.elapsed_secs();
     
     if !stats.show_stats {
@@ -397,11 +415,12 @@ fn log_advanced_status(
         info!("   📐 Resolution: {}x{}", 
               shared_resources.config.width, 
               shared_resources.config.height);
-        info!("   🎚️  Double buffering: {}", shared_resources.config.enable_double_buffering);
+        info!("   🎚️  Buffers: {} ({:?})",
+              shared_resources.config.buffer_count,
+              shared_resources.config.swap_policy);
         info!("   🔗 Active textures: {}", shared_resources.texture_handles.len());
-        info!("   🚦 Semaphores: {} render-finished, {} consumer-ready", 
-              shared_resources.render_finished_semaphores.len(),
-              shared_resources.consumer_ready_semaphores.len());
+        info!("   🚦 Sync: {:?}, consumer-ready semaphores created per consumer", 
+              shared_resources.config.sync_mode);
         
         if shared_resources.ipc_handler.is_some() {
             info!("   📡 IPC: Active - consumers can connect");
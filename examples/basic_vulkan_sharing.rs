@@ -18,7 +18,7 @@
 use bevy::prelude::*;
 use bevy::window::WindowPlugin;
 use bevy::winit::WinitPlugin;
-use bevy_external_surface::{VulkanSharingPlugin, VulkanSharingConfig, SharedVulkanResources};
+use bevy_external_surface::{BufferSwapPolicy, ColorSpace, FrameSyncMode, MemoryExportMode, SharedSurfaceId, SharedSurfaceTarget, SharedSurfaces, VulkanSharingPlugin, VulkanSharingConfig};
 use ash::vk;
 
 fn main() {
@@ -36,12 +36,18 @@ fn main() {
         )
         // Simple Vulkan sharing configuration
         .add_plugins(VulkanSharingPlugin {
+            id: SharedSurfaceId::default(),
             config: VulkanSharingConfig {
                 width: 1280,
                 height: 720,
                 format: vk::Format::B8G8R8A8_SRGB,
                 ipc_socket_path: Some("/tmp/basic_vulkan_sharing.sock".to_string()),
-                enable_double_buffering: false,  // Keep it simple - single texture
+                buffer_count: 1,  // Keep it simple - single texture
+                swap_policy: BufferSwapPolicy::Fifo,
+                sync_mode: FrameSyncMode::Binary,
+                memory_export: MemoryExportMode::OpaqueFd,
+                color_space: ColorSpace::Srgb,
+                share_depth: false,
             },
         })
         .add_systems(Startup, setup_basic_scene)
@@ -59,6 +65,7 @@ fn setup_basic_scene(
     // Simple camera setup
     commands.spawn((
         Camera3d::default(),
+        SharedSurfaceTarget::default(),
         Transform::from_xyz(3.0, 3.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
     ));
     
@@ -127,9 +134,13 @@ fn rotate_objects(
 
 fn log_status(
     time: Res<Time>,
-    shared_resources: Res<SharedVulkanResources>,
+    shared_surfaces: Res<SharedSurfaces>,
     mut last_log_time: Local<f32>,
 ) {
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
     let current_time = time.elapsed_secs();
     
     // Log every 3 seconds
@@ -142,7 +153,7 @@ fn log_status(
               shared_resources.config.width, 
               shared_resources.config.height);
         info!("   🖼️  Shared textures: {}", shared_resources.texture_handles.len());
-        info!("   🔄 Double buffering: {}", shared_resources.config.enable_double_buffering);
+        info!("   🔄 Buffers: {}", shared_resources.config.buffer_count);
         
         if shared_resources.ipc_handler.is_some() {
             info!("   🔗 IPC: Ready for consumers");
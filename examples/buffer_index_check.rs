@@ -0,0 +1,171 @@
+// Buffer Index Check
+//
+// Renders a few hundred frames into a shared surface and checks, in the render
+// world, that the shared texture each camera renders into is the buffer announced
+// to consumers for that frame, and that rendering actually cycles through the
+// buffers. Exits with a non-zero status on the first failure.
+//
+// Requires a Vulkan device, run with:
+//   cargo run --example buffer_index_check
+
+use bevy::prelude::*;
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::render::{
+    camera::{ExtractedCamera, NormalizedRenderTarget},
+    Render, RenderApp, RenderSet,
+};
+use bevy_external_surface::{
+    BufferSwapPolicy, ColorSpace, FrameSyncMode, MemoryExportMode, SharedSurfaceId, SharedSurfaceTarget,
+    SharedSurfaces, VulkanSharingConfig, VulkanSharingPlugin,
+};
+use ash::vk;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const BUFFER_COUNT: u32 = 3;
+const FRAMES_TO_CHECK: u32 = 300;
+// Frames after which the check gives up, setup may have failed
+const MAX_FRAMES: u32 = FRAMES_TO_CHECK * 2;
+
+// Written by the render world, read by the main world
+#[derive(Resource, Clone, Default)]
+struct CheckResults(Arc<Mutex<Results>>);
+
+#[derive(Default)]
+struct Results {
+    checked_frames: u32,
+    // Buffers rendered into so far, one bit each
+    rendered_buffers: u64,
+    failure: Option<String>,
+}
+
+fn main() {
+    let results = CheckResults::default();
+    
+    let mut app = App::new();
+    app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            // Cameras listen for window events even without windows
+            bevy::window::WindowPlugin {
+                primary_window: None,
+                exit_condition: bevy::window::ExitCondition::DontExit,
+                ..default()
+            },
+            AssetPlugin::default(),
+            bevy::render::RenderPlugin::default(),
+            bevy::render::texture::ImagePlugin::default(),
+            bevy::core_pipeline::CorePipelinePlugin,
+            bevy::pbr::PbrPlugin::default(),
+            bevy::log::LogPlugin::default(),
+        ))
+        .add_plugins(VulkanSharingPlugin {
+            id: SharedSurfaceId::default(),
+            config: VulkanSharingConfig {
+                width: 256,
+                height: 256,
+                format: vk::Format::R8G8B8A8_UNORM,
+                // No consumers needed, the announced index is the one checked below
+                ipc_socket_path: None,
+                buffer_count: BUFFER_COUNT,
+                swap_policy: BufferSwapPolicy::Fifo,
+                sync_mode: FrameSyncMode::Binary,
+                memory_export: MemoryExportMode::OpaqueFd,
+                color_space: ColorSpace::Linear,
+                share_depth: false,
+            },
+        })
+        .insert_resource(results.clone())
+        .add_systems(Startup, setup_camera)
+        .add_systems(Update, report_results);
+    
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app.insert_resource(results);
+    render_app.add_systems(Render, check_rendered_buffer.in_set(RenderSet::Queue));
+    
+    let exit = app.run();
+    if exit.is_error() {
+        std::process::exit(1);
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera3d::default(),
+        SharedSurfaceTarget::default(),
+        Transform::from_xyz(0.0, 0.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn check_rendered_buffer(
+    shared_surfaces: Res<SharedSurfaces>,
+    cameras: Query<&ExtractedCamera>,
+    results: Res<CheckResults>,
+) {
+    let Ok(mut results) = results.0.lock() else {
+        return;
+    };
+    let Some(surface) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    if surface.texture_handles.is_empty() || results.failure.is_some() {
+        return;
+    }
+    
+    for camera in &cameras {
+        let Some(NormalizedRenderTarget::TextureView(handle)) = camera.target else {
+            continue;
+        };
+        let Some(rendered) = surface.texture_handles.iter().position(|&shared| shared == handle) else {
+            continue;
+        };
+        
+        let announced = surface.current_buffer_index;
+        if rendered != announced {
+            results.failure = Some(format!(
+                "frame {}: camera rendered into buffer {} but buffer {} is announced",
+                results.checked_frames,
+                rendered,
+                announced,
+            ));
+            return;
+        }
+        
+        results.rendered_buffers |= 1 << rendered;
+    }
+    
+    results.checked_frames += 1;
+}
+
+fn report_results(
+    results: Res<CheckResults>,
+    mut exit: EventWriter<AppExit>,
+    mut frames: Local<u32>,
+) {
+    let Ok(results) = results.0.lock() else {
+        return;
+    };
+    *frames += 1;
+    
+    if let Some(failure) = &results.failure {
+        error!("Buffer index check failed, {}", failure);
+        exit.write(AppExit::from_code(1));
+        return;
+    }
+    if results.checked_frames < FRAMES_TO_CHECK {
+        if *frames >= MAX_FRAMES {
+            error!("Buffer index check failed, only {} frames rendered into the shared surface", results.checked_frames);
+            exit.write(AppExit::from_code(1));
+        }
+        return;
+    }
+    
+    let rendered_buffers = results.rendered_buffers.count_ones();
+    if rendered_buffers != BUFFER_COUNT {
+        error!("Buffer index check failed, only {} of {} buffers were rendered into", rendered_buffers, BUFFER_COUNT);
+        exit.write(AppExit::from_code(1));
+        return;
+    }
+    
+    info!("Buffer index check passed, {} frames cycled through all {} buffers", results.checked_frames, BUFFER_COUNT);
+    exit.write(AppExit::Success);
+}
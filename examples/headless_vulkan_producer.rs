@@ -21,9 +21,15 @@
 use bevy::prelude::*;
 // No window/winit imports needed for headless
 use bevy_external_surface::{
+    BufferSwapPolicy,
+    ColorSpace,
+    FrameSyncMode,
+    MemoryExportMode,
     VulkanSharingPlugin, 
     VulkanSharingConfig, 
-    SharedVulkanResources,
+    SharedSurfaceId,
+    SharedSurfaceTarget,
+    SharedSurfaces,
     headless::{HeadlessRenderPlugin, HeadlessRenderSettings},
 };
 use ash::vk;
@@ -57,12 +63,18 @@ fn main() {
         })
         // Vulkan sharing for external consumption
         .add_plugins(VulkanSharingPlugin {
+            id: SharedSurfaceId::default(),
             config: VulkanSharingConfig {
                 width: 1600,
                 height: 900,
                 format: vk::Format::B8G8R8A8_SRGB,
                 ipc_socket_path: Some("/tmp/headless_vulkan_sharing.sock".to_string()),
-                enable_double_buffering: true,
+                buffer_count: 2,
+                swap_policy: BufferSwapPolicy::Fifo,
+                sync_mode: FrameSyncMode::Binary,
+                memory_export: MemoryExportMode::OpaqueFd,
+                color_space: ColorSpace::Srgb,
+                share_depth: false,
             },
         })
         .insert_resource(HeadlessStats {
@@ -99,6 +111,7 @@ fn setup_headless_scene(
     // Fixed camera for consistent output
     commands.spawn((
         Camera3d::default(),
+        SharedSurfaceTarget::default(),
         Transform::from_xyz(5.0, 5.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
         HeadlessCamera,
     ));
@@ -306,9 +319,13 @@ fn update_headless_scene(
 fn monitor_headless_performance(
     time: Res<Time>,
     stats: Res<HeadlessStats>,
-    shared_resources: Res<SharedVulkanResources>,
+    shared_surfaces: Res<SharedSurfaces>,
     mut last_report: Local<f32>,
 ) {
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
     let current_time = time.elapsed_secs();
     
     // Report every 5 seconds for server environments
@@ -326,7 +343,9 @@ fn monitor_headless_performance(
         info!("   📐 Output resolution: {}x{}", 
               shared_resources.config.width,
               shared_resources.config.height);
-        info!("   🔄 Double buffering: {}", shared_resources.config.enable_double_buffering);
+        info!("   🔄 Buffers: {} ({:?})",
+              shared_resources.config.buffer_count,
+              shared_resources.config.swap_policy);
         
         if shared_resources.ipc_handler.is_some() {
             info!("   📡 IPC: Active - ready for consumer connections");
@@ -339,7 +358,7 @@ fn monitor_headless_performance(
             warn!("   ⚠️  Performance below target - consider reducing scene complexity");
         }
         
-        if frame_count > 0 && frame_count % 1000 == 0 {
+        if frame_count > 0 && frame_count.is_multiple_of(1000) {
             info!("   🎯 Milestone: {} frames rendered successfully", frame_count);
         }
     }
@@ -392,22 +411,3 @@ fn handle_shutdown_signals(
     }
 }
 
-// Error types for server environments
-#[derive(Debug)]
-enum HeadlessError {
-    RenderingFailed(String),
-    ResourceExhaustion,
-    NetworkError(String),
-}
-
-impl std::fmt::Display for HeadlessError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            HeadlessError::RenderingFailed(msg) => write!(f, "Rendering failed: {}", msg),
-            HeadlessError::ResourceExhaustion => write!(f, "System resources exhausted"),
-            HeadlessError::NetworkError(msg) => write!(f, "Network error: {}", msg),
-        }
-    }
-}
-
-impl std::error::Error for HeadlessError {}
\ No newline at end of file
@@ -5,8 +5,8 @@
 // that consumes the shared textures for display or further processing.
 //
 // Key concepts demonstrated:
-// 1. IPC connection via Unix socket
-// 2. Receiving shared memory file descriptors
+// 1. Connecting with VulkanSharingClient, which performs the handshake
+// 2. Importing the shared memory into VkImages on our own Vulkan device
 // 3. Frame synchronization using semaphores
 // 4. Proper error handling and resource management
 //
@@ -14,27 +14,94 @@
 // 1. Run the vulkan_sharing_producer example first
 // 2. Run this consumer example in a separate terminal
 // 3. The consumer will connect and receive frames from the producer
+//
+// Opaque fd memory can only be imported by the same physical device that exported
+// it. This example simply picks the first one.
 
-use std::os::fd::RawFd;
-use std::os::unix::net::UnixStream;
-use std::os::unix::io::AsRawFd;
 use std::time::{Duration, Instant};
-use nix::sys::socket::{self, ControlMessageOwned, MsgFlags};
-use serde::{Deserialize, Serialize};
+use ash::vk;
+use bevy_external_surface::vulkan_sharing::protocol::{Capabilities, MemoryHandleType, Metadata};
+use bevy_external_surface::{AcquiredFrame, FrameSyncMode, VulkanSharingClient, VulkanSharingClientConfig};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct IPCMetadata {
-    width: u32,
-    height: u32,
-    format: u32,
-    memory_fds: Vec<RawFd>,
+// Minimal headless Vulkan setup standing in for the application's own renderer
+struct VulkanContext {
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    queue: vk::Queue,
+    // Whether DMA-BUF producers can be imported from
+    dma_buf: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct IPCFrameInfo {
-    buffer_index: usize,
-    render_finished_semaphore_fd: Option<RawFd>,
-    consumer_ready_semaphore_fd: Option<RawFd>,
+impl VulkanContext {
+    fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let entry = unsafe { ash::Entry::load()? };
+        
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(c"vulkan_sharing_consumer")
+            .api_version(vk::API_VERSION_1_2);
+        let instance_info = vk::InstanceCreateInfo::default().application_info(&app_info);
+        let instance = unsafe { entry.create_instance(&instance_info, None)? };
+        
+        let physical_device = unsafe { instance.enumerate_physical_devices()? }
+            .into_iter()
+            .next()
+            .ok_or("No Vulkan device found")?;
+        
+        let queue_family_index = unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+            .iter()
+            .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .ok_or("No graphics queue found")? as u32;
+        
+        let priorities = [1.0];
+        let queue_infos = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family_index)
+            .queue_priorities(&priorities)];
+        
+        // The client needs both fd extensions, and timeline mode needs the feature
+        let mut extensions = vec![
+            ash::khr::external_memory_fd::NAME.as_ptr(),
+            ash::khr::external_semaphore_fd::NAME.as_ptr(),
+        ];
+        
+        // DMA-BUF import is optional, producers exporting opaque fds don't need it
+        let available = unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+        let dma_buf_extensions = [ash::ext::external_memory_dma_buf::NAME, ash::ext::image_drm_format_modifier::NAME];
+        let dma_buf = dma_buf_extensions.iter().all(|name| {
+            available.iter().any(|extension| extension.extension_name_as_c_str() == Ok(*name))
+        });
+        if dma_buf {
+            extensions.extend(dma_buf_extensions.iter().map(|name| name.as_ptr()));
+        }
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default().timeline_semaphore(true);
+        
+        let device_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(&extensions)
+            .push_next(&mut vulkan_12_features);
+        let device = unsafe { instance.create_device(physical_device, &device_info, None)? };
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        
+        Ok(Self {
+            _entry: entry,
+            instance,
+            physical_device,
+            device,
+            queue,
+            dma_buf,
+        })
+    }
+}
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
 }
 
 struct ConsumerStats {
@@ -80,16 +147,31 @@ impl ConsumerStats {
 fn main() {
     println!("🚀 Vulkan Sharing Consumer Starting...");
     println!("📺 This example receives shared Vulkan textures from a Bevy producer");
+    
+    let vulkan = match VulkanContext::new() {
+        Ok(vulkan) => vulkan,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize Vulkan: {}", e);
+            return;
+        }
+    };
+    
     println!("🔗 Connecting to producer via Unix socket...");
     
-    let socket_path = "/tmp/bevy_vulkan_sharing.sock";
+    let mut config = VulkanSharingClientConfig {
+        name: "vulkan_sharing_consumer".to_string(),
+        ..Default::default()
+    };
+    if vulkan.dma_buf {
+        config.capabilities = config.capabilities | Capabilities::DMA_BUF;
+    }
     let mut stats = ConsumerStats::new();
     
     // Attempt connection with retry logic
-    let mut stream = match connect_with_retry(socket_path, 5) {
-        Some(stream) => {
-            println!("✅ Connected to producer at {}", socket_path);
-            stream
+    let mut client = match connect_with_retry(&config, &vulkan, 5) {
+        Some(client) => {
+            println!("✅ Connected to producer at {}", config.socket_path);
+            client
         }
         None => {
             eprintln!("❌ Failed to connect to producer after multiple attempts");
@@ -98,52 +180,95 @@ fn main() {
         }
     };
     
-    // Receive initial metadata with shared memory handles
-    match receive_metadata(&mut stream) {
-        Ok(metadata) => {
-            println!("📋 Received shared surface metadata:");
-            println!("   📐 Resolution: {}x{}", metadata.width, metadata.height);
-            println!("   🎨 Vulkan Format: {} ({})", metadata.format, format_name(metadata.format));
-            println!("   💾 Shared Memory FDs: {} buffers", metadata.memory_fds.len());
-            
-            // Print implementation guidance
-            print_vulkan_integration_guide(&metadata);
-            
-            println!("🎬 Starting frame processing loop...");
-            println!("   Press Ctrl+C to exit");
-            
-            // Main frame processing loop
-            loop {
-                let frame_start = Instant::now();
-                
-                match receive_frame_info(&mut stream) {
-                    Ok(frame_info) => {
-                        process_frame(&frame_info, &metadata);
-                        
-                        // Record processing stats
-                        stats.record_frame(frame_start.elapsed());
-                    }
-                    Err(e) => {
-                        eprintln!("❌ Failed to receive frame info: {}", e);
-                        eprintln!("🔄 Producer may have disconnected");
-                        break;
-                    }
-                }
+    let metadata = client.metadata().clone();
+    println!("📋 Received shared surface metadata:");
+    println!("   🆔 Consumer id: {}", metadata.consumer_id);
+    println!("   📐 Resolution: {}x{}", metadata.width, metadata.height);
+    println!("   🎨 Vulkan Format: {} ({})", metadata.format, format_name(metadata.format));
+    println!("   🌈 Color space: {:?}, camera HDR: {}, tonemapping: {:?}",
+             metadata.color_space, metadata.camera_hdr, metadata.tonemapping);
+    if let Some(depth_format) = metadata.depth_format {
+        println!("   🕳️  Depth shared as {} (near {}, far {})",
+                 format_name(depth_format), metadata.projection.near, metadata.projection.far);
+    }
+    println!("   💾 Imported {} shared images", client.images().len());
+    if metadata.sync_mode == FrameSyncMode::Timeline {
+        println!("   ⏱️  Producer uses timeline semaphores");
+    }
+    if metadata.memory_handle_type == MemoryHandleType::DmaBuf {
+        for (i, layout) in metadata.dma_buf_layouts.iter().enumerate() {
+            println!("   🧩 Buffer {}: DMA-BUF fourcc {:#010x}, modifier {:#x}, planes {:?}",
+                     i, layout.fourcc, layout.modifier, layout.planes);
+        }
+    }
+    
+    // Print implementation guidance
+    print_vulkan_integration_guide(&metadata);
+    
+    println!("🎬 Starting frame processing loop...");
+    println!("   Press Ctrl+C to exit");
+    
+    let mut size = (metadata.width, metadata.height);
+    
+    // Main frame processing loop
+    loop {
+        let frame_start = Instant::now();
+        
+        let frame = match client.acquire_frame() {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("❌ Failed to receive frame: {}", e);
+                eprintln!("🔄 Producer may have disconnected");
+                break;
             }
+        };
+        
+        if let Err(e) = process_frame(&vulkan, &frame) {
+            eprintln!("❌ Failed to process frame: {}", e);
+            break;
         }
-        Err(e) => {
-            eprintln!("❌ Failed to receive metadata: {}", e);
-            eprintln!("🔧 Check if producer is configured correctly");
+        
+        // The signal is submitted and our work has completed, so the producer may wait
+        // on it and reuse the buffer
+        if let Err(e) = client.release_frame(frame) {
+            eprintln!("❌ Failed to release frame: {}", e);
+            break;
+        }
+        
+        // Acquiring imports the new images when the producer resizes. The old ones
+        // are freed once nothing reads from them anymore.
+        let metadata = client.metadata();
+        if (metadata.width, metadata.height) != size {
+            size = (metadata.width, metadata.height);
+            println!("📐 Producer resized shared images to {}x{}", size.0, size.1);
+            
+            unsafe {
+                let _ = vulkan.device.queue_wait_idle(vulkan.queue);
+            }
+            client.destroy_retired_images();
         }
+        
+        // Record processing stats
+        stats.record_frame(frame_start.elapsed());
     }
     
+    // Nothing may use the imported images or semaphores once the client is dropped
+    unsafe {
+        let _ = vulkan.device.device_wait_idle();
+    }
+    drop(client);
+    
     println!("🏁 Consumer shutting down");
 }
 
-fn connect_with_retry(socket_path: &str, max_retries: u32) -> Option<UnixStream> {
+fn connect_with_retry(
+    config: &VulkanSharingClientConfig,
+    vulkan: &VulkanContext,
+    max_retries: u32,
+) -> Option<VulkanSharingClient> {
     for attempt in 1..=max_retries {
-        match UnixStream::connect(socket_path) {
-            Ok(stream) => return Some(stream),
+        match VulkanSharingClient::connect(config, &vulkan.instance, vulkan.physical_device, &vulkan.device) {
+            Ok(client) => return Some(client),
             Err(e) => {
                 if attempt < max_retries {
                     println!("🔄 Connection attempt {} failed: {}", attempt, e);
@@ -158,179 +283,81 @@ fn connect_with_retry(socket_path: &str, max_retries: u32) -> Option<UnixStream>
     None
 }
 
-fn process_frame(frame_info: &IPCFrameInfo, _metadata: &IPCMetadata) {
-    println!("🎞️  Frame received - Buffer index: {}", frame_info.buffer_index);
+fn process_frame(vulkan: &VulkanContext, frame: &AcquiredFrame) -> Result<(), vk::Result> {
+    println!("🎞️  Frame received - Buffer index: {}", frame.buffer_index);
     
-    // In a real Vulkan consumer application, you would:
-    // 
-    // 1. WAIT FOR PRODUCER: Import and wait on render_finished_semaphore_fd
-    //    - vkImportSemaphoreFdKHR() to import the semaphore
-    //    - vkWaitSemaphores() or use it in vkQueueSubmit() wait stage
-    //
-    // 2. USE SHARED TEXTURE: Access the shared memory
-    //    - The shared VkDeviceMemory is already bound to a VkImage
-    //    - Create VkImageView if needed for your pipeline
-    //    - Use in compute shader, graphics pipeline, or copy operations
-    //
-    // 3. SIGNAL COMPLETION: Signal consumer_ready_semaphore_fd when done
-    //    - Import consumer_ready_semaphore_fd if provided
-    //    - vkQueueSubmit() with signal semaphore, or vkSignalSemaphore()
-    //
-    // 4. SYNCHRONIZATION: This ensures proper frame pacing and prevents tearing
+    if frame.frame_value > 0 {
+        println!("   ⏱️  Timeline frame value: {}", frame.frame_value);
+    }
+    
+    // A real application would record commands reading frame.image here, e.g. a
+    // copy, a sampled draw or a compute dispatch, into the same submission. This
+    // example only does the synchronization: wait until the producer is done
+    // rendering, then signal that we are done reading.
+    let wait_semaphores = [frame.wait_semaphore];
+    let signal_semaphores = [frame.signal_semaphore];
+    let stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+    
+    // Values are ignored for binary semaphores
+    let values = [frame.frame_value];
+    let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
+        .wait_semaphore_values(&values)
+        .signal_semaphore_values(&values);
+    
+    let submit_info = vk::SubmitInfo::default()
+        .wait_semaphores(&wait_semaphores)
+        .wait_dst_stage_mask(&stages)
+        .signal_semaphores(&signal_semaphores)
+        .push_next(&mut timeline_info);
+    
+    unsafe { vulkan.device.queue_submit(vulkan.queue, &[submit_info], vk::Fence::null()) }?;
     
     // Simulate realistic processing time (rendering/copying/displaying)
     let processing_time = Duration::from_millis(8); // Simulate ~8ms processing
     std::thread::sleep(processing_time);
     
-    // In real implementation, you'd handle semaphore FDs here
-    if frame_info.render_finished_semaphore_fd.is_some() {
-        // Would wait on this semaphore before using texture
-        println!("   🚦 Render finished semaphore available");
-    }
+    // Releasing destroys a binary mode frame's wait semaphore, so the submission
+    // has to be done. A real application would track it with a fence.
+    unsafe { vulkan.device.queue_wait_idle(vulkan.queue) }?;
     
-    if frame_info.consumer_ready_semaphore_fd.is_some() {
-        // Would signal this semaphore after processing
-        println!("   ✅ Consumer ready semaphore available");
-    }
+    Ok(())
 }
 
-fn format_name(vk_format: u32) -> &'static str {
+fn format_name(vk_format: u32) -> String {
     // Convert Vulkan format enum to human-readable name
-    match vk_format {
-        37 => "B8G8R8A8_SRGB",
-        44 => "B8G8R8A8_UNORM", 
-        43 => "R8G8B8A8_SRGB",
-        42 => "R8G8B8A8_UNORM", // Fixed duplicate case
-        _ => "Unknown",
-    }
+    format!("{:?}", vk::Format::from_raw(vk_format as i32))
 }
 
-fn print_vulkan_integration_guide(metadata: &IPCMetadata) {
+fn print_vulkan_integration_guide(metadata: &Metadata) {
     println!("\n🔧 Integration Guide for Real Vulkan Applications:");
-    println!("   1. Import Memory FDs:");
-    println!("      - Use vkImportMemoryFdKHR() for each memory_fd");
-    println!("      - Create VkImage with matching dimensions ({}x{})", metadata.width, metadata.height);
-    println!("      - Bind imported memory with vkBindImageMemory()");
+    println!("   1. Shared Images:");
+    println!("      - VulkanSharingClient imported one VkImage per buffer ({}x{})", metadata.width, metadata.height);
+    println!("      - AcquiredFrame::image is the one the producer just rendered");
+    println!("      - AcquiredFrame::depth_image holds its reverse Z depth if shared");
     println!();
     println!("   2. Create Image Views:");
     println!("      - vkCreateImageView() for each imported image");
     println!("      - Use format {} (Vulkan enum {})", format_name(metadata.format), metadata.format);
+    println!("      - Values are {:?}, convert before presenting in another color space", metadata.color_space);
+    println!();
+    println!("   3. Synchronization ({:?} mode):", metadata.sync_mode);
+    println!("      - Wait on AcquiredFrame::wait_semaphore before using the texture");
+    println!("      - Signal AcquiredFrame::signal_semaphore after processing");
+    if metadata.sync_mode == FrameSyncMode::Timeline {
+        println!("      - Both are used at AcquiredFrame::frame_value");
+        println!("      - Call release_frame() once the signal is submitted");
+    } else {
+        println!("      - Call release_frame() once the submission has completed");
+    }
     println!();
-    println!("   3. Synchronization:");
-    println!("      - Import semaphore FDs with vkImportSemaphoreFdKHR()");
-    println!("      - Wait on render_finished before using texture");
-    println!("      - Signal consumer_ready after processing");
+    println!("   4. Resizing:");
+    println!("      - request_resize() asks the producer for a new size");
+    println!("      - New images are imported while acquiring, use AcquiredFrame::image");
+    println!("      - Call destroy_retired_images() once work on the old ones completed");
     println!();
-    println!("   4. Usage Examples:");
+    println!("   5. Usage Examples:");
     println!("      - Texture binding: Use in fragment shaders");
     println!("      - Compute processing: Dispatch compute shaders");
     println!("      - Display: Present to swapchain or copy to window");
     println!();
 }
-
-fn receive_metadata(stream: &mut UnixStream) -> Result<IPCMetadata, Box<dyn std::error::Error>> {
-    println!("📥 Receiving metadata from producer...");
-    
-    let mut buf = vec![0u8; 1024];
-    let mut cmsg_buf = nix::cmsg_space!([RawFd; 10]);
-    
-    let fd = stream.as_raw_fd();
-    
-    let bytes_received;
-    let mut received_fds = Vec::new();
-    
-    {
-        let mut iov = [std::io::IoSliceMut::new(&mut buf)];
-        let msg = socket::recvmsg::<()>(
-            fd,
-            &mut iov,
-            Some(&mut cmsg_buf),
-            MsgFlags::empty(),
-        )?;
-        
-        bytes_received = msg.bytes;
-        
-        if bytes_received == 0 {
-            return Err("Producer closed connection during metadata transfer".into());
-        }
-        
-        // Extract file descriptors from control messages
-        if let Ok(cmsgs) = msg.cmsgs() {
-            for cmsg in cmsgs {
-                if let ControlMessageOwned::ScmRights(fds) = cmsg {
-                    received_fds.extend_from_slice(&fds);
-                    println!("   📎 Received {} memory file descriptors", fds.len());
-                }
-            }
-        }
-    }
-    
-    // Deserialize metadata
-    let mut metadata: IPCMetadata = bincode::deserialize(&buf[..bytes_received])
-        .map_err(|e| format!("Failed to deserialize metadata: {}", e))?;
-    
-    metadata.memory_fds = received_fds;
-    
-    // Validate metadata
-    if metadata.width == 0 || metadata.height == 0 {
-        return Err("Invalid texture dimensions received".into());
-    }
-    
-    if metadata.memory_fds.is_empty() {
-        return Err("No memory file descriptors received".into());
-    }
-    
-    println!("   ✅ Metadata received and validated");
-    Ok(metadata)
-}
-
-fn receive_frame_info(stream: &mut UnixStream) -> Result<IPCFrameInfo, Box<dyn std::error::Error>> {
-    let mut buf = vec![0u8; 256];
-    let mut cmsg_buf = nix::cmsg_space!([RawFd; 2]);
-    
-    let fd = stream.as_raw_fd();
-    
-    let bytes_received;
-    let mut received_fds = Vec::new();
-    
-    {
-        let mut iov = [std::io::IoSliceMut::new(&mut buf)];
-        let msg = socket::recvmsg::<()>(
-            fd,
-            &mut iov,
-            Some(&mut cmsg_buf),
-            MsgFlags::empty(),
-        )?;
-        
-        bytes_received = msg.bytes;
-        
-        if bytes_received == 0 {
-            return Err("Producer closed connection".into());
-        }
-        
-        // Extract semaphore FDs if present
-        if let Ok(cmsgs) = msg.cmsgs() {
-            for cmsg in cmsgs {
-                if let ControlMessageOwned::ScmRights(fds) = cmsg {
-                    received_fds.extend_from_slice(&fds);
-                }
-            }
-        }
-    }
-    
-    // Deserialize frame info
-    let mut frame_info: IPCFrameInfo = bincode::deserialize(&buf[..bytes_received])
-        .map_err(|e| format!("Failed to deserialize frame info: {}", e))?;
-    
-    // Assign received FDs to semaphores
-    if !received_fds.is_empty() {
-        if received_fds.len() >= 1 {
-            frame_info.render_finished_semaphore_fd = Some(received_fds[0]);
-        }
-        if received_fds.len() >= 2 {
-            frame_info.consumer_ready_semaphore_fd = Some(received_fds[1]);
-        }
-    }
-    
-    Ok(frame_info)
-}
\ No newline at end of file
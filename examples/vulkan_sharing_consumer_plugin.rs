@@ -0,0 +1,99 @@
+// Vulkan Sharing Consumer Plugin Example
+//
+// This example shows frames rendered by another Bevy process in a window of its own.
+// VulkanSharingConsumerPlugin connects to the producer, imports its shared textures
+// and keeps an Image handle pointed at the latest frame, which is drawn here as a
+// sprite. No pixels are copied on the way.
+//
+// To test this example:
+// 1. Run the vulkan_sharing_producer example first
+// 2. Run this example in a separate terminal
+// 3. The producer's scene appears in this window, and reappears if the producer
+//    is restarted
+
+use bevy::prelude::*;
+use bevy_external_surface::{
+    SharedFrameReceived, SharedFrameReceiver, VulkanSharingClientConfig, VulkanSharingConsumerConfig,
+    VulkanSharingConsumerPlugin,
+};
+use std::time::Duration;
+
+fn main() {
+    println!("📺 Vulkan Sharing Consumer Plugin Example");
+    println!("   Socket: /tmp/bevy_vulkan_sharing.sock");
+    
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Vulkan Sharing Consumer".to_string(),
+                resolution: (1280.0, 720.0).into(),
+                ..default()
+            }),
+            ..default()
+        }))
+        .add_plugins(VulkanSharingConsumerPlugin {
+            config: VulkanSharingConsumerConfig {
+                client: VulkanSharingClientConfig {
+                    socket_path: "/tmp/bevy_vulkan_sharing.sock".to_string(),
+                    name: "vulkan_sharing_consumer_plugin".to_string(),
+                    ..default()
+                },
+                reconnect_interval: Duration::from_secs(1),
+            },
+        })
+        .add_systems(Startup, setup_display)
+        .add_systems(Update, (fit_display_to_window, log_received_frames))
+        .run();
+}
+
+#[derive(Component)]
+struct SharedFrameDisplay;
+
+fn setup_display(mut commands: Commands, receiver: Res<SharedFrameReceiver>) {
+    commands.spawn(Camera2d);
+    
+    // The handle stays the same across frames and reconnects
+    commands.spawn((
+        Sprite {
+            image: receiver.image.clone(),
+            custom_size: Some(Vec2::new(1280.0, 720.0)),
+            ..default()
+        },
+        SharedFrameDisplay,
+    ));
+}
+
+fn fit_display_to_window(
+    windows: Query<&Window>,
+    mut displays: Query<&mut Sprite, With<SharedFrameDisplay>>,
+) {
+    let Ok(window) = windows.single() else {
+        return;
+    };
+    
+    for mut sprite in &mut displays {
+        let size = window.size();
+        if sprite.custom_size != Some(size) {
+            sprite.custom_size = Some(size);
+        }
+    }
+}
+
+fn log_received_frames(
+    receiver: Res<SharedFrameReceiver>,
+    mut events: EventReader<SharedFrameReceived>,
+    mut frames: Local<u64>,
+    mut last_log: Local<Option<std::time::Instant>>,
+) {
+    *frames += events.read().count() as u64;
+    
+    let now = std::time::Instant::now();
+    if last_log.is_none_or(|last| now.duration_since(last) >= Duration::from_secs(2)) {
+        if receiver.is_connected() {
+            info!("Received {} frames from the producer", *frames);
+        } else {
+            info!("Waiting for a producer...");
+        }
+        *last_log = Some(now);
+    }
+}
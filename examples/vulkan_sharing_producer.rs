@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 use bevy::log::{info, warn};
 use bevy::app::ScheduleRunnerPlugin;
-use bevy_external_surface::vulkan_sharing::{VulkanSharingPlugin, VulkanSharingConfig, SharedVulkanResources};
+use bevy_external_surface::vulkan_sharing::{
+    BufferSwapPolicy, ColorSpace, ConsumerConnected, ConsumerDisconnected, FrameSyncMode, MemoryExportMode,
+    ResizeSharedSurface, SharedSurfaceId, SharedSurfaceTarget, SharedSurfaces, VulkanSharingPlugin,
+    VulkanSharingConfig,
+};
 use ash::vk;
 use std::time::Duration;
 
@@ -17,23 +21,29 @@ fn main() {
             AssetPlugin::default(),
             bevy::render::RenderPlugin::default(),
             bevy::render::texture::ImagePlugin::default(),
-            bevy::core_pipeline::CorePipelinePlugin::default(),
+            bevy::core_pipeline::CorePipelinePlugin,
             bevy::pbr::PbrPlugin::default(),
             bevy::log::LogPlugin::default(),
             ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0)),
         ))
         // Add our Vulkan sharing plugin
         .add_plugins(VulkanSharingPlugin {
+            id: SharedSurfaceId::default(),
             config: VulkanSharingConfig {
                 width: 1920,
                 height: 1080,
                 format: vk::Format::B8G8R8A8_SRGB,
                 ipc_socket_path: Some("/tmp/bevy_vulkan_sharing.sock".to_string()),
-                enable_double_buffering: true,
+                buffer_count: 2,
+                swap_policy: BufferSwapPolicy::Fifo,
+                sync_mode: FrameSyncMode::Binary,
+                memory_export: MemoryExportMode::OpaqueFd,
+                color_space: ColorSpace::Srgb,
+                share_depth: false,
             },
         })
         .add_systems(Startup, setup_scene)
-        .add_systems(Update, (animate_scene, log_sharing_status, handle_input))
+        .add_systems(Update, (animate_scene, log_sharing_status, log_consumer_connections, handle_input))
         .run();
 }
 
@@ -46,6 +56,7 @@ fn setup_scene(
     // The VulkanSharingPlugin will automatically redirect camera output to the shared textures
     commands.spawn((
         Camera3d::default(),
+        SharedSurfaceTarget::default(),
         Transform::from_xyz(0.0, 6.0, 12.0).looking_at(Vec3::ZERO, Vec3::Y),
     ));
 
@@ -179,9 +190,13 @@ fn animate_scene(
 
 fn log_sharing_status(
     time: Res<Time>,
-    shared_resources: Res<SharedVulkanResources>,
+    shared_surfaces: Res<SharedSurfaces>,
     mut last_log_time: Local<f32>,
 ) {
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
     let current_time = time.elapsed_secs();
     
     // Log status every 2 seconds
@@ -203,14 +218,45 @@ fn log_sharing_status(
     }
 }
 
+fn log_consumer_connections(
+    mut connected: EventReader<ConsumerConnected>,
+    mut disconnected: EventReader<ConsumerDisconnected>,
+) {
+    for event in connected.read() {
+        info!("Consumer {} attached - sending frames", event.consumer_id);
+    }
+    
+    for event in disconnected.read() {
+        info!("Consumer {} detached", event.consumer_id);
+    }
+}
+
 fn handle_input(
     keys: Res<ButtonInput<KeyCode>>,
-    mut shared_resources: ResMut<SharedVulkanResources>,
+    mut shared_surfaces: ResMut<SharedSurfaces>,
+    mut resize_events: EventWriter<ResizeSharedSurface>,
 ) {
     if keys.just_pressed(KeyCode::Space) {
         info!("Manual buffer swap triggered");
-        // Note: swap_buffers is called automatically in the render loop
-        shared_resources.current_buffer_index = (shared_resources.current_buffer_index + 1) % shared_resources.texture_handles.len();
+        // Note: swap_buffers is also called automatically every frame
+        if let Some(shared_resources) = shared_surfaces.get_mut(SharedSurfaceId::default()) {
+            shared_resources.swap_buffers();
+        }
+    }
+    
+    let Some(shared_resources) = shared_surfaces.get(SharedSurfaceId::default()) else {
+        return;
+    };
+    
+    if keys.just_pressed(KeyCode::KeyR) {
+        // Toggle between full and half resolution, consumers get the new images
+        let (width, height) = if shared_resources.config.width == 1920 { (960, 540) } else { (1920, 1080) };
+        info!("Resizing shared textures to {}x{}", width, height);
+        resize_events.write(ResizeSharedSurface {
+            surface: shared_resources.id,
+            width,
+            height,
+        });
     }
     
     if keys.just_pressed(KeyCode::Escape) {
@@ -222,15 +268,16 @@ fn handle_input(
         info!("=== Vulkan Sharing Info ===");
         info!("Resolution: {}x{}", shared_resources.config.width, shared_resources.config.height);
         info!("Format: {:?}", shared_resources.config.format);
-        info!("Double Buffering: {}", shared_resources.config.enable_double_buffering);
+        info!("Buffers: {} ({:?})", shared_resources.config.buffer_count, shared_resources.config.swap_policy);
         info!("Active Textures: {}", shared_resources.texture_handles.len());
         info!("Current Buffer Index: {}", shared_resources.current_buffer_index);
         if let Some(socket_path) = &shared_resources.config.ipc_socket_path {
             info!("IPC Socket: {}", socket_path);
         }
-        info!("Memory FDs: {}", shared_resources.memory_fds.len());
+        info!("Memory FDs: {}", shared_resources.memory_fds().len());
         info!("=== Controls ===");
         info!("SPACE: Manual buffer swap");
+        info!("R: Toggle half resolution");
         info!("I: Show info");
         info!("ESC: Exit");
     }
@@ -1,28 +1,57 @@
 use bevy::{
     prelude::*,
+    core_pipeline::blit::{BlitPipeline, BlitPipelineKey},
     render::{
+        camera::{CameraUpdateSystem, ManualTextureView, ManualTextureViews, RenderTarget},
+        graph::CameraDriverLabel,
         render_asset::RenderAssetUsages,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel},
         render_resource::{
-            Extent3d, TextureDimension, TextureFormat, TextureUsages,
+            BindGroupEntries, CachedRenderPipelineId, Extent3d, LoadOp, Operations, PipelineCache,
+            RenderPassColorAttachment, RenderPassDescriptor, SpecializedRenderPipelines, StoreOp, TextureDimension,
+            TextureFormat, TextureUsages, TextureView,
         },
-        renderer::{RenderDevice, RenderQueue},
+        renderer::{render_system, RenderAdapter, RenderContext, RenderDevice, RenderInstance, RenderQueue},
         Extract, Render, RenderApp, RenderSet,
     },
 };
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use wgpu::{Surface, SurfaceConfiguration};
 
-use crate::Result;
+use crate::texture_view_handles::{ManualTextureViewHandles, ManualTextureViewHandlesPlugin, ReservedHandles};
+use crate::{ExternalRenderTarget, ExternalSurfaceError, Result};
 
+// Somewhere frames rendered by Bevy end up. Cameras render into an offscreen
+// texture of the surface's size and format, which is drawn into the view
+// `acquire_frame` returns once they are done. `submit` then hands the frame on,
+// presenting it, encoding it, sending it over the network and so on.
+//
+// Surfaces are shared between the main and render world through an `Arc`, so all
+// methods take `&self` and may be called from either.
 pub trait ExternalSurface: Send + Sync + 'static {
-    fn as_image(&self) -> Option<Handle<Image>>;
+    fn size(&self) -> (u32, u32);
     
-    fn as_raw_texture(&self) -> Option<&wgpu::Texture>;
+    fn format(&self) -> TextureFormat;
     
-    fn present(&self) -> Result<()>;
+    // Takes effect from the next frame, cameras follow the new size
+    fn resize(&self, width: u32, height: u32) -> Result<()>;
     
-    fn resize(&mut self, width: u32, height: u32) -> Result<()>;
+    // View of this frame's destination, in `format`, called from the render graph
+    // after the cameras have run. The frame is drawn into it with
+    // `render_context`'s encoder.
+    fn acquire_frame(&self, render_context: &RenderContext) -> Result<TextureView>;
+    
+    // Called once the commands drawing the acquired frame have been submitted,
+    // only for frames that were acquired
+    fn submit(&self, render_queue: &RenderQueue) -> Result<()>;
+    
+    // Surfaces that are a Bevy image are rendered into directly instead, without
+    // acquiring or submitting frames
+    fn as_image(&self) -> Option<Handle<Image>> {
+        None
+    }
 }
 
 #[derive(Clone)]
@@ -44,6 +73,14 @@ impl std::fmt::Debug for SurfaceTarget {
 
 pub trait WindowHandle: HasWindowHandle + HasDisplayHandle + Send + Sync {}
 
+// Marks cameras rendering into the `ExternalRenderTarget` set up by
+// `ExternalSurfacePlugin`. `RenderToExternal` takes precedence over it.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ExternalSurfaceCamera;
+
+// Creates the surface for `target` once the renderer is up and inserts it as the
+// `ExternalRenderTarget`. New windows and textures get `size` and `format`, raw
+// textures keep their own. Add it after the `RenderPlugin`.
 pub struct ExternalSurfacePlugin {
     pub target: SurfaceTarget,
     pub size: (u32, u32),
@@ -62,35 +99,128 @@ impl Default for ExternalSurfacePlugin {
 
 impl Plugin for ExternalSurfacePlugin {
     fn build(&self, app: &mut App) {
-        let render_app = app.sub_app_mut(RenderApp);
+        // Shared with whatever else registers manual texture views
+        if !app.is_plugin_added::<ManualTextureViewHandlesPlugin>() {
+            app.add_plugins(ManualTextureViewHandlesPlugin);
+        }
+        app.init_resource::<ExternalSurfaceViews>();
+        
+        // Cameras are pointed at the surface before their render targets are resolved
+        app.add_systems(PostUpdate, route_external_cameras.before(CameraUpdateSystem));
+        
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            warn!("ExternalSurfacePlugin added without the RenderPlugin, nothing will be drawn into the surface");
+            return;
+        };
         
         render_app
+            .init_resource::<ExtractedExternalSurfaces>()
             .add_systems(ExtractSchedule, extract_external_surfaces)
             .add_systems(
                 Render,
                 (
-                    prepare_external_surfaces.in_set(RenderSet::PrepareResources),
-                    render_to_external_surfaces.in_set(RenderSet::Render),
+                    prepare_external_surfaces.in_set(RenderSet::Prepare),
+                    // Frames are drawn into the surfaces by the render graph, which
+                    // has to be submitted first
+                    submit_external_surfaces.in_set(RenderSet::Render).after(render_system),
                 ),
             );
+        
+        // Surfaces are drawn into once every camera has rendered
+        let Some(mut render_graph) = render_app.world_mut().get_resource_mut::<RenderGraph>() else {
+            warn!("No render graph found, nothing will be drawn into the external surface");
+            return;
+        };
+        render_graph.add_node(ExternalSurfaceLabel, ExternalSurfaceNode);
+        render_graph.add_node_edge(CameraDriverLabel, ExternalSurfaceLabel);
+    }
+    
+    // The render resources only exist once the RenderPlugin has finished
+    fn finish(&self, app: &mut App) {
+        match create_surface(app, &self.target, self.size, self.format) {
+            Ok(target) => {
+                let (width, height) = target.handle.size();
+                info!("Created external surface for {:?} at {}x{}", self.target, width, height);
+                app.insert_resource(target);
+            }
+            Err(e) => {
+                error!("Failed to create external surface for {:?}: {}", self.target, e);
+            }
+        }
     }
 }
 
+fn create_surface(
+    app: &mut App,
+    target: &SurfaceTarget,
+    size: (u32, u32),
+    format: TextureFormat,
+) -> Result<ExternalRenderTarget> {
+    let missing_renderer = || ExternalSurfaceError::SurfaceCreationFailed(
+        "Render resources not found, add ExternalSurfacePlugin after the RenderPlugin".into(),
+    );
+    
+    let handle: Arc<dyn ExternalSurface> = match target {
+        SurfaceTarget::Window(window) => {
+            let world = app.world();
+            let (Some(adapter), Some(device)) = (
+                world.get_resource::<RenderAdapter>(),
+                world.get_resource::<RenderDevice>(),
+            ) else {
+                return Err(missing_renderer());
+            };
+            // Bevy only keeps its instance in the render world
+            let instance = app.get_sub_app(RenderApp)
+                .and_then(|render_app| render_app.world().get_resource::<RenderInstance>())
+                .ok_or_else(missing_renderer)?;
+            
+            Arc::new(WindowSurface::new(window.clone(), instance, adapter, device, size, format)?)
+        }
+        // The default handle stands for a new image
+        SurfaceTarget::Texture(image) if *image == Handle::default() => {
+            let mut images = app.world_mut().get_resource_mut::<Assets<Image>>().ok_or_else(missing_renderer)?;
+            
+            Arc::new(TextureSurface::new(&mut images, size, format))
+        }
+        SurfaceTarget::Texture(image) => {
+            Arc::new(TextureSurface::from_image(image.clone(), size, format))
+        }
+        SurfaceTarget::RawTexture(texture) => {
+            Arc::new(RawTextureSurface::new(texture.clone()))
+        }
+    };
+    
+    Ok(ExternalRenderTarget { handle })
+}
+
+// A window created outside of Bevy, by GTK, SDL, Qt or the like, which frames are
+// presented to
 pub struct WindowSurface {
     surface: Surface<'static>,
+    state: Mutex<WindowState>,
+    // The surface refers to the window, which has to outlive it
+    _window: Arc<dyn WindowHandle>,
+}
+
+struct WindowState {
     config: SurfaceConfiguration,
-    size: (u32, u32),
+    // Set by `resize`, applied when the next frame is acquired
+    reconfigure: bool,
+    // Acquired and waiting for `submit`
+    frame: Option<wgpu::SurfaceTexture>,
 }
 
 impl WindowSurface {
+    // Takes Bevy's render resources: the surface has to come from the instance the
+    // device was created with, and be presentable by its adapter.
     pub fn new(
         window: Arc<dyn WindowHandle>,
+        instance: &RenderInstance,
+        adapter: &RenderAdapter,
         device: &RenderDevice,
         size: (u32, u32),
         format: TextureFormat,
     ) -> Result<Self> {
-        let instance = wgpu::Instance::default();
-        
         // We need to use raw window and display handles directly
         let surface = unsafe {
             let raw_window = window.window_handle().map_err(|e| 
@@ -108,14 +238,44 @@ impl WindowSurface {
                 .map_err(|e| crate::ExternalSurfaceError::SurfaceCreationFailed(e.to_string()))?
         };
         
+        let capabilities = surface.get_capabilities(adapter);
+        let unsupported = |reason: String| Err(ExternalSurfaceError::SurfaceCreationFailed(reason));
+        
+        // Everything is empty if the adapter cannot present to the window at all
+        if capabilities.formats.is_empty() {
+            return unsupported("The render adapter cannot present to this window".into());
+        }
+        if !capabilities.formats.contains(&format) {
+            return unsupported(format!(
+                "Window surface does not support {:?}, supported formats are {:?}",
+                format,
+                capabilities.formats,
+            ));
+        }
+        // AutoVsync picks FifoRelaxed or falls back to Fifo
+        let present_mode = wgpu::PresentMode::AutoVsync;
+        if !capabilities.present_modes.contains(&wgpu::PresentMode::Fifo) {
+            return unsupported(format!(
+                "Window surface does not support vsync, supported present modes are {:?}",
+                capabilities.present_modes,
+            ));
+        }
+        let alpha_mode = if capabilities.alpha_modes.contains(&wgpu::CompositeAlphaMode::Opaque) {
+            wgpu::CompositeAlphaMode::Opaque
+        } else if let Some(&alpha_mode) = capabilities.alpha_modes.first() {
+            alpha_mode
+        } else {
+            return unsupported("Window surface supports no alpha mode".into());
+        };
+        
         let config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
-            format: format.into(),
+            format,
             width: size.0,
             height: size.1,
-            present_mode: wgpu::PresentMode::AutoVsync,
+            present_mode,
             desired_maximum_frame_latency: 2,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            alpha_mode,
             view_formats: vec![],
         };
         
@@ -123,37 +283,86 @@ impl WindowSurface {
         
         Ok(Self {
             surface,
-            config,
-            size,
+            state: Mutex::new(WindowState {
+                config,
+                reconfigure: false,
+                frame: None,
+            }),
+            _window: window,
         })
     }
+    
+    fn state(&self) -> std::sync::MutexGuard<'_, WindowState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 impl ExternalSurface for WindowSurface {
-    fn as_image(&self) -> Option<Handle<Image>> {
-        None
+    fn size(&self) -> (u32, u32) {
+        let state = self.state();
+        (state.config.width, state.config.height)
     }
     
-    fn as_raw_texture(&self) -> Option<&wgpu::Texture> {
-        None
+    fn format(&self) -> TextureFormat {
+        self.state().config.format
     }
     
-    fn present(&self) -> Result<()> {
+    fn resize(&self, width: u32, height: u32) -> Result<()> {
+        if width == 0 || height == 0 {
+            return Err(ExternalSurfaceError::SurfaceCreationFailed(
+                format!("Cannot resize window surface to {}x{}", width, height),
+            ));
+        }
+        
+        let mut state = self.state();
+        state.config.width = width;
+        state.config.height = height;
+        state.reconfigure = true;
+        
         Ok(())
     }
     
-    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
-        self.size = (width, height);
-        self.config.width = width;
-        self.config.height = height;
+    // The next swapchain texture, reconfiguring the surface once if it went out of
+    // date, for example because the window was resized behind our back
+    fn acquire_frame(&self, render_context: &RenderContext) -> Result<TextureView> {
+        let device = render_context.render_device().wgpu_device();
+        let mut state = self.state();
+        
+        if std::mem::take(&mut state.reconfigure) {
+            self.surface.configure(device, &state.config);
+        }
+        
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface.configure(device, &state.config);
+                
+                self.surface.get_current_texture()
+                    .map_err(|e| ExternalSurfaceError::PresentFailed(e.to_string()))?
+            }
+            Err(e) => return Err(ExternalSurfaceError::PresentFailed(e.to_string())),
+        };
+        
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        state.frame = Some(frame);
+        
+        Ok(view.into())
+    }
+    
+    fn submit(&self, _render_queue: &RenderQueue) -> Result<()> {
+        if let Some(frame) = self.state().frame.take() {
+            frame.present();
+        }
+        
         Ok(())
     }
 }
 
+// A Bevy image cameras render into, to be sampled by other cameras or read back
 pub struct TextureSurface {
     image: Handle<Image>,
-    texture: Option<Arc<wgpu::Texture>>,
-    size: (u32, u32),
+    size: Mutex<(u32, u32)>,
+    format: TextureFormat,
 }
 
 impl TextureSurface {
@@ -182,57 +391,394 @@ impl TextureSurface {
         
         let handle = images.add(image);
         
+        Self::from_image(handle, size, format)
+    }
+    
+    // Renders into an existing image, which has to allow RENDER_ATTACHMENT
+    pub fn from_image(image: Handle<Image>, size: (u32, u32), format: TextureFormat) -> Self {
         Self {
-            image: handle,
-            texture: None,
-            size,
+            image,
+            size: Mutex::new(size),
+            format,
         }
     }
 }
 
 impl ExternalSurface for TextureSurface {
+    fn size(&self) -> (u32, u32) {
+        *self.size.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    
+    fn format(&self) -> TextureFormat {
+        self.format
+    }
+    
+    // The image itself is resized by `route_external_cameras`
+    fn resize(&self, width: u32, height: u32) -> Result<()> {
+        *self.size.lock().unwrap_or_else(PoisonError::into_inner) = (width, height);
+        Ok(())
+    }
+    
+    fn acquire_frame(&self, _render_context: &RenderContext) -> Result<TextureView> {
+        Err(ExternalSurfaceError::SurfaceCreationFailed(
+            "Texture surfaces are rendered into directly".into(),
+        ))
+    }
+    
+    fn submit(&self, _render_queue: &RenderQueue) -> Result<()> {
+        Ok(())
+    }
+    
     fn as_image(&self) -> Option<Handle<Image>> {
         Some(self.image.clone())
     }
+}
+
+// A texture created and sized by the caller, with RENDER_ATTACHMENT usage, which
+// frames are drawn into
+pub struct RawTextureSurface {
+    texture: Arc<wgpu::Texture>,
+}
+
+impl RawTextureSurface {
+    pub fn new(texture: Arc<wgpu::Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl ExternalSurface for RawTextureSurface {
+    fn size(&self) -> (u32, u32) {
+        let extent = self.texture.size();
+        (extent.width, extent.height)
+    }
     
-    fn as_raw_texture(&self) -> Option<&wgpu::Texture> {
-        self.texture.as_ref().map(|t| t.as_ref())
+    fn format(&self) -> TextureFormat {
+        self.texture.format()
     }
     
-    fn present(&self) -> Result<()> {
-        Ok(())
+    fn resize(&self, width: u32, height: u32) -> Result<()> {
+        Err(ExternalSurfaceError::SurfaceCreationFailed(format!(
+            "Cannot resize a raw texture to {}x{}, its owner has to replace it",
+            width,
+            height,
+        )))
+    }
+    
+    fn acquire_frame(&self, _render_context: &RenderContext) -> Result<TextureView> {
+        Ok(self.texture.create_view(&wgpu::TextureViewDescriptor::default()).into())
     }
     
-    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
-        self.size = (width, height);
+    fn submit(&self, _render_queue: &RenderQueue) -> Result<()> {
         Ok(())
     }
 }
 
-fn extract_external_surfaces(
-    mut commands: Commands,
-    surfaces: Extract<Query<(Entity, &crate::RenderToExternal)>>,
+// Offscreen texture cameras render a surface's frames into, registered as a
+// manual texture view
+struct SurfaceView {
+    surface: Arc<dyn ExternalSurface>,
+    handles: ReservedHandles,
+    // Replaced when the surface changes size or format
+    view: Option<ManualTextureView>,
+}
+
+#[derive(Resource, Default)]
+struct ExternalSurfaceViews {
+    views: Vec<SurfaceView>,
+}
+
+impl ExternalSurfaceViews {
+    // What cameras render into `surface` through: its image, or the manual texture
+    // view of an offscreen texture matching its current size and format
+    fn render_target(
+        &mut self,
+        surface: &Arc<dyn ExternalSurface>,
+        manual_texture_views: &mut ResMut<ManualTextureViews>,
+        handle_allocator: &ManualTextureViewHandles,
+        render_device: &RenderDevice,
+    ) -> Option<RenderTarget> {
+        if let Some(image) = surface.as_image() {
+            return Some(RenderTarget::Image(image.into()));
+        }
+        
+        let index = match self.views.iter().position(|view| Arc::ptr_eq(&view.surface, surface)) {
+            Some(index) => index,
+            None => {
+                let Some(handles) = handle_allocator.reserve(1, manual_texture_views) else {
+                    warn!("No free manual texture view handles left for an external surface");
+                    return None;
+                };
+                
+                self.views.push(SurfaceView {
+                    surface: surface.clone(),
+                    handles,
+                    view: None,
+                });
+                self.views.len() - 1
+            }
+        };
+        let view = &mut self.views[index];
+        let handle = view.handles.handle(0);
+        
+        let (width, height) = surface.size();
+        let size = UVec2::new(width, height);
+        let format = surface.format();
+        
+        let up_to_date = view.view.as_ref()
+            .is_some_and(|view| view.size == size && view.format == format);
+        if !up_to_date && width > 0 && height > 0 {
+            let texture = render_device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("external_surface_texture"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                // Sampled when drawn into the surface
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let manual_view = ManualTextureView {
+                texture_view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                size,
+                format,
+            };
+            
+            manual_texture_views.insert(handle, manual_view.clone());
+            view.view = Some(manual_view);
+        }
+        
+        Some(RenderTarget::TextureView(handle))
+    }
+    
+    // Unregisters the views of surfaces no camera renders into anymore, releasing
+    // their handles
+    fn release_unused(
+        &mut self,
+        used: &[&Arc<dyn ExternalSurface>],
+        manual_texture_views: &mut ResMut<ManualTextureViews>,
+    ) {
+        self.views.retain(|view| {
+            let in_use = used.iter().any(|&surface| Arc::ptr_eq(surface, &view.surface));
+            if !in_use {
+                for handle in view.handles.handles() {
+                    manual_texture_views.remove(&handle);
+                }
+            }
+            in_use
+        });
+    }
+}
+
+fn same_render_target(a: &RenderTarget, b: &RenderTarget) -> bool {
+    match (a, b) {
+        (RenderTarget::TextureView(a), RenderTarget::TextureView(b)) => a == b,
+        (RenderTarget::Image(a), RenderTarget::Image(b)) => a.handle == b.handle,
+        _ => false,
+    }
+}
+
+type ExternalCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Camera, Option<&'static crate::RenderToExternal>),
+    Or<(With<ExternalSurfaceCamera>, With<crate::RenderToExternal>)>,
+>;
+
+// Points every camera at its surface. Checked each frame, so cameras follow
+// surfaces that were resized.
+fn route_external_cameras(
+    target: Option<Res<ExternalRenderTarget>>,
+    mut surface_views: ResMut<ExternalSurfaceViews>,
+    mut manual_texture_views: ResMut<ManualTextureViews>,
+    mut images: ResMut<Assets<Image>>,
+    handle_allocator: Res<ManualTextureViewHandles>,
+    render_device: Option<Res<RenderDevice>>,
+    mut cameras: ExternalCameraQuery,
 ) {
-    for (entity, render_to_external) in &surfaces {
-        commands
-            .entity(entity)
-            .insert(render_to_external.clone());
+    let Some(render_device) = render_device else {
+        return;
+    };
+    
+    let mut used: Vec<&Arc<dyn ExternalSurface>> = target.as_deref().map(|target| &target.handle).into_iter().collect();
+    
+    for (mut camera, render_to_external) in cameras.iter_mut() {
+        let surface = match (render_to_external, target.as_deref()) {
+            (Some(render_to_external), _) => &render_to_external.target,
+            (None, Some(target)) => &target.handle,
+            (None, None) => continue,
+        };
+        
+        let Some(render_target) = surface_views.render_target(
+            surface,
+            &mut manual_texture_views,
+            &handle_allocator,
+            &render_device,
+        ) else {
+            continue;
+        };
+        used.push(surface);
+        
+        if let RenderTarget::Image(image_target) = &render_target {
+            resize_image(&mut images, &image_target.handle, surface.size());
+        }
+        
+        if !same_render_target(&camera.target, &render_target) {
+            camera.target = render_target;
+        }
     }
+    
+    surface_views.release_unused(&used, &mut manual_texture_views);
 }
 
-fn prepare_external_surfaces(
-    mut surfaces: Query<&mut crate::RenderToExternal>,
-    render_device: Res<RenderDevice>,
+// Follows a resize of a surface that is an image
+fn resize_image(images: &mut Assets<Image>, handle: &Handle<Image>, (width, height): (u32, u32)) {
+    let Some(image) = images.get(handle) else {
+        return;
+    };
+    if image.size() == UVec2::new(width, height) || width == 0 || height == 0 {
+        return;
+    }
+    
+    if let Some(image) = images.get_mut(handle) {
+        image.resize(Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        });
+    }
+}
+
+// A surface cameras rendered into this frame, with the texture they rendered into
+struct ExtractedSurface {
+    surface: Arc<dyn ExternalSurface>,
+    source: TextureView,
+    format: TextureFormat,
+    pipeline: Option<CachedRenderPipelineId>,
+    // Set once the frame is acquired, so it is submitted
+    acquired: AtomicBool,
+}
+
+#[derive(Resource, Default)]
+struct ExtractedExternalSurfaces {
+    surfaces: Vec<ExtractedSurface>,
+}
+
+fn extract_external_surfaces(
+    mut extracted: ResMut<ExtractedExternalSurfaces>,
+    surface_views: Extract<Res<ExternalSurfaceViews>>,
 ) {
+    extracted.surfaces = surface_views.views.iter()
+        .filter_map(|view| {
+            let manual_view = view.view.as_ref()?;
+            
+            Some(ExtractedSurface {
+                surface: view.surface.clone(),
+                source: manual_view.texture_view.clone(),
+                format: manual_view.format,
+                pipeline: None,
+                acquired: AtomicBool::new(false),
+            })
+        })
+        .collect();
 }
 
-fn render_to_external_surfaces(
-    surfaces: Query<&crate::RenderToExternal>,
-    render_queue: Res<RenderQueue>,
+fn prepare_external_surfaces(
+    mut extracted: ResMut<ExtractedExternalSurfaces>,
+    blit_pipeline: Option<Res<BlitPipeline>>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<BlitPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
 ) {
-    for surface in &surfaces {
-        if let Err(e) = surface.target.present() {
-            warn!("Failed to present external surface: {}", e);
+    let Some(blit_pipeline) = blit_pipeline else {
+        return;
+    };
+    
+    for surface in extracted.surfaces.iter_mut() {
+        surface.pipeline = Some(pipelines.specialize(&pipeline_cache, &blit_pipeline, BlitPipelineKey {
+            texture_format: surface.format,
+            blend_state: None,
+            samples: 1,
+        }));
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct ExternalSurfaceLabel;
+
+// Draws what the cameras rendered into each surface's acquired frame
+struct ExternalSurfaceNode;
+
+impl Node for ExternalSurfaceNode {
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> std::result::Result<(), NodeRunError> {
+        let (Some(extracted), Some(blit_pipeline)) = (
+            world.get_resource::<ExtractedExternalSurfaces>(),
+            world.get_resource::<BlitPipeline>(),
+        ) else {
+            return Ok(());
+        };
+        let pipeline_cache = world.resource::<PipelineCache>();
+        
+        for surface in &extracted.surfaces {
+            // Still compiling during the first frames
+            let Some(pipeline) = surface.pipeline.and_then(|id| pipeline_cache.get_render_pipeline(id)) else {
+                continue;
+            };
+            
+            let frame = match surface.surface.acquire_frame(render_context) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("Failed to acquire external surface frame: {}", e);
+                    continue;
+                }
+            };
+            surface.acquired.store(true, Ordering::Relaxed);
+            
+            let bind_group = render_context.render_device().create_bind_group(
+                "external_surface_bind_group",
+                &blit_pipeline.texture_bind_group,
+                &BindGroupEntries::sequential((&surface.source, &blit_pipeline.sampler)),
+            );
+            
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("external_surface_pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &frame,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Default::default()),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_render_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        
+        Ok(())
+    }
+}
+
+fn submit_external_surfaces(extracted: Res<ExtractedExternalSurfaces>, render_queue: Res<RenderQueue>) {
+    for surface in &extracted.surfaces {
+        if !surface.acquired.swap(false, Ordering::Relaxed) {
+            continue;
+        }
+        
+        if let Err(e) = surface.surface.submit(&render_queue) {
+            warn!("Failed to submit external surface frame: {}", e);
         }
     }
 }
\ No newline at end of file
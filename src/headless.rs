@@ -31,18 +31,11 @@ impl Default for HeadlessRenderSettings {
     }
 }
 
+#[derive(Default)]
 pub struct HeadlessRenderPlugin {
     pub settings: HeadlessRenderSettings,
 }
 
-impl Default for HeadlessRenderPlugin {
-    fn default() -> Self {
-        Self {
-            settings: HeadlessRenderSettings::default(),
-        }
-    }
-}
-
 impl Plugin for HeadlessRenderPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(self.settings.clone());
@@ -70,7 +63,7 @@ impl PluginGroup for HeadlessPlugins {
             .add(bevy::asset::AssetPlugin::default())
             .add(bevy::render::RenderPlugin::default())
             .add(bevy::render::texture::ImagePlugin::default())
-            .add(bevy::core_pipeline::CorePipelinePlugin::default())
+            .add(bevy::core_pipeline::CorePipelinePlugin)
             .add(bevy::pbr::PbrPlugin::default());
         
         group
@@ -1,5 +1,6 @@
 pub mod external_surface;
 pub mod headless;
+pub mod texture_view_handles;
 pub mod vulkan_interop;
 pub mod vulkan_sharing;
 
@@ -7,10 +8,27 @@ use bevy::prelude::*;
 use std::sync::Arc;
 use thiserror::Error;
 
-pub use external_surface::{ExternalSurface, ExternalSurfacePlugin, SurfaceTarget};
+pub use external_surface::{
+    ExternalSurface, ExternalSurfaceCamera, ExternalSurfacePlugin, RawTextureSurface, SurfaceTarget, TextureSurface,
+    WindowSurface,
+};
 pub use headless::{HeadlessRenderPlugin, HeadlessRenderSettings};
-pub use vulkan_interop::{ExternalMemoryHandle, VulkanExternalTexture};
-pub use vulkan_sharing::{VulkanSharingPlugin, VulkanSharingConfig, SharedVulkanResources};
+pub use texture_view_handles::{ManualTextureViewHandles, ManualTextureViewHandlesPlugin, ReservedHandles};
+pub use vulkan_interop::{
+    vk_format_to_wgpu, wgpu_format_to_vk, ExternalMemoryHandle, ExternalSemaphore, SemaphoreHandleType,
+    SemaphoreKind, VulkanExternalTexture,
+};
+pub use vulkan_sharing::{
+    BufferSwapPolicy, ColorSpace, ConsumerConnected, ConsumerDisconnected, FrameSyncMode, MemoryExportMode,
+    ResizeSharedSurface, SharedSurfaceId, SharedSurfaceTarget, SharedSurfaces, SharedVulkanResources,
+    VulkanSharingConfig, VulkanSharingPlugin,
+};
+#[cfg(unix)]
+pub use vulkan_sharing::client::{AcquiredFrame, PendingConnection, VulkanSharingClient, VulkanSharingClientConfig};
+#[cfg(unix)]
+pub use vulkan_sharing::consumer::{
+    SharedFrameReceived, SharedFrameReceiver, VulkanSharingConsumerConfig, VulkanSharingConsumerPlugin,
+};
 
 #[derive(Debug, Error)]
 pub enum ExternalSurfaceError {
@@ -28,17 +46,31 @@ pub enum ExternalSurfaceError {
     
     #[error("Invalid texture format")]
     InvalidTextureFormat,
+    
+    #[error("Synchronization failed: {0}")]
+    SynchronizationFailed(String),
+    
+    #[error("Failed to present surface: {0}")]
+    PresentFailed(String),
+    
+    #[error("IPC peer disconnected")]
+    IpcDisconnected,
+    
+    #[error("IPC protocol error: {0}")]
+    IpcProtocolError(String),
 }
 
 pub type Result<T> = std::result::Result<T, ExternalSurfaceError>;
 
+// The surface set up by `ExternalSurfacePlugin`. Its size and format are read from
+// `handle`, which follows resizes.
 #[derive(Resource, Clone)]
 pub struct ExternalRenderTarget {
     pub handle: Arc<dyn ExternalSurface>,
-    pub size: (u32, u32),
-    pub format: wgpu::TextureFormat,
 }
 
+// Makes the camera it is added to render into `target`, which several cameras
+// may share. Overrides `ExternalSurfaceCamera`.
 #[derive(Component, Clone)]
 pub struct RenderToExternal {
     pub target: Arc<dyn ExternalSurface>,
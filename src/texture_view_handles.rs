@@ -0,0 +1,171 @@
+// Hands out `ManualTextureViewHandle`s that nobody else uses.
+//
+// Handles are plain numbers, so two pieces of code registering views under
+// `ManualTextureViewHandle(0)` silently replace each other's. Code sharing an app
+// with other libraries reserves a range here instead and registers its views under
+// the handles in it. Ranges are taken from the upper half of the handle space,
+// leaving low numbers to code picking its own, and skip handles already registered
+// in `ManualTextureViews`. Views still registered under a range when it is dropped
+// are removed by `ManualTextureViewHandlesPlugin` before the range is handed out
+// again.
+
+use bevy::{
+    prelude::*,
+    render::camera::{ManualTextureViewHandle, ManualTextureViews},
+};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+// First handle handed out
+pub const FIRST_RESERVED_HANDLE: u32 = 1 << 31;
+
+// Keeps `ManualTextureViewHandles` and removes the views of dropped reservations.
+// Added by the plugins of this crate, only needed when reserving handles without
+// them.
+pub struct ManualTextureViewHandlesPlugin;
+
+impl Plugin for ManualTextureViewHandlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ManualTextureViewHandles>();
+        app.add_systems(Last, remove_released_views);
+    }
+}
+
+fn remove_released_views(
+    handle_allocator: Res<ManualTextureViewHandles>,
+    manual_texture_views: Option<ResMut<ManualTextureViews>>,
+) {
+    if let Some(mut manual_texture_views) = manual_texture_views
+        && handle_allocator.has_released()
+    {
+        handle_allocator.remove_released_views(&mut manual_texture_views);
+    }
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct ManualTextureViewHandles {
+    ranges: Arc<Mutex<Ranges>>,
+}
+
+#[derive(Default)]
+struct Ranges {
+    // Sorted by start
+    reserved: Vec<Range<u32>>,
+    // Dropped but still reserved until the views registered under them are removed
+    released: Vec<Range<u32>>,
+}
+
+impl ManualTextureViewHandles {
+    // Reserves `count` consecutive handles, released again when the returned range
+    // is dropped. Fails once the handle space is exhausted.
+    pub fn reserve(&self, count: u32, manual_texture_views: &ManualTextureViews) -> Option<ReservedHandles> {
+        let mut ranges = self.ranges.lock().ok()?;
+        let reserved = &mut ranges.reserved;
+        
+        let mut start = FIRST_RESERVED_HANDLE;
+        loop {
+            let end = start.checked_add(count)?;
+            let candidate = start..end;
+            
+            // Skip past whatever the candidate overlaps and try again from there
+            let overlapping_range = reserved.iter()
+                .find(|range| range.start < candidate.end && candidate.start < range.end)
+                .map(|range| range.end);
+            let overlapping_view = manual_texture_views.keys()
+                .filter(|handle| candidate.contains(&handle.0))
+                .map(|handle| handle.0 + 1)
+                .max();
+            
+            match overlapping_range.max(overlapping_view) {
+                Some(next_start) => start = next_start,
+                None => {
+                    let position = reserved.partition_point(|range| range.start < candidate.start);
+                    reserved.insert(position, candidate.clone());
+                    
+                    return Some(ReservedHandles {
+                        range: candidate,
+                        ranges: self.ranges.clone(),
+                    });
+                }
+            }
+        }
+    }
+    
+    // Unregisters the views under dropped ranges and frees the ranges
+    pub fn remove_released_views(&self, manual_texture_views: &mut ManualTextureViews) {
+        let Ok(mut ranges) = self.ranges.lock() else {
+            return;
+        };
+        
+        let released = std::mem::take(&mut ranges.released);
+        for range in &released {
+            for handle in range.clone() {
+                manual_texture_views.remove(&ManualTextureViewHandle(handle));
+            }
+        }
+        ranges.reserved.retain(|range| !released.contains(range));
+    }
+    
+    fn has_released(&self) -> bool {
+        self.ranges.lock().is_ok_and(|ranges| !ranges.released.is_empty())
+    }
+}
+
+// Consecutive handles reserved through `ManualTextureViewHandles`. Views registered
+// under them are removed after it is dropped.
+pub struct ReservedHandles {
+    range: Range<u32>,
+    ranges: Arc<Mutex<Ranges>>,
+}
+
+impl ReservedHandles {
+    pub fn len(&self) -> u32 {
+        self.range.end - self.range.start
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+    
+    // The handle at `index`, which has to be below `len`
+    pub fn handle(&self, index: u32) -> ManualTextureViewHandle {
+        assert!(index < self.len(), "handle {} out of {} reserved", index, self.len());
+        
+        ManualTextureViewHandle(self.range.start + index)
+    }
+    
+    pub fn handles(&self) -> impl Iterator<Item = ManualTextureViewHandle> + '_ {
+        self.range.clone().map(ManualTextureViewHandle)
+    }
+}
+
+impl Drop for ReservedHandles {
+    fn drop(&mut self) {
+        if let Ok(mut ranges) = self.ranges.lock() {
+            ranges.released.push(self.range.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn dropped_ranges_are_reused_once_swept() {
+        let handle_allocator = ManualTextureViewHandles::default();
+        let mut manual_texture_views = ManualTextureViews::default();
+        
+        let first = handle_allocator.reserve(2, &manual_texture_views).unwrap();
+        assert_eq!(first.handle(0), ManualTextureViewHandle(FIRST_RESERVED_HANDLE));
+        drop(first);
+        
+        // Its views may still be registered, so the range stays taken
+        let second = handle_allocator.reserve(2, &manual_texture_views).unwrap();
+        assert_eq!(second.handle(0), ManualTextureViewHandle(FIRST_RESERVED_HANDLE + 2));
+        
+        handle_allocator.remove_released_views(&mut manual_texture_views);
+        let third = handle_allocator.reserve(2, &manual_texture_views).unwrap();
+        assert_eq!(third.handle(0), ManualTextureViewHandle(FIRST_RESERVED_HANDLE));
+    }
+}
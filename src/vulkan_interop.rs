@@ -10,9 +10,16 @@ use bevy::{
         Render, RenderApp, RenderSet,
     },
 };
+use ash::vk;
+use std::ffi::CStr;
 use std::sync::Arc;
-use wgpu_hal::{api::Vulkan as VulkanApi};
+use std::time::Duration;
+use wgpu_hal::api::Vulkan as VulkanApi;
 
+#[cfg(unix)]
+use std::os::fd::RawFd;
+
+use crate::vulkan_sharing::find_memory_type;
 use crate::{ExternalSurfaceError, Result};
 
 #[derive(Debug, Clone)]
@@ -91,50 +98,96 @@ impl VulkanExternalTexture {
         size: Extent3d,
         format: TextureFormat,
     ) -> Result<Self> {
-        let wgpu_device = render_device.wgpu_device();
-        
-        // Check Vulkan backend
-        let is_vulkan = unsafe {
-            wgpu_device.as_hal::<VulkanApi, _, bool>(|device| {
-                device.is_some()
-            })
-        };
-        
-        if !is_vulkan {
+        #[cfg(unix)]
+        let ExternalMemoryHandle::OpaqueFd(fd) = handle;
+        #[cfg(not(unix))]
+        {
+            let _ = handle;
             return Err(ExternalSurfaceError::UnsupportedBackend(
-                "Vulkan backend required for external memory".into(),
+                "Only opaque fd memory import is implemented".into(),
             ));
         }
         
-        // Create texture descriptor
-        let texture_desc = TextureDescriptor {
-            label: Some("imported_external_texture"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format,
-            usage: TextureUsages::RENDER_ATTACHMENT
-                | TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST,
-            view_formats: &[],
-        };
-        
-        // For now, create a regular texture (full implementation would use ash for import)
-        let texture = render_device.create_texture(&texture_desc);
-        
-        Ok(Self {
-            texture: Arc::new(texture),
-            memory_handle: Some(handle),
-            semaphore_handle: None,
-            size,
-            format,
-        })
+        // From here on we own the fd: Vulkan takes it over on a successful import,
+        // otherwise it has to be closed before returning
+        #[cfg(unix)]
+        {
+            let vk_format = match wgpu_format_to_vk(format) {
+                Some(vk_format) => vk_format,
+                None => {
+                    unsafe { libc::close(fd) };
+                    return Err(ExternalSurfaceError::InvalidTextureFormat);
+                }
+            };
+            
+            let wgpu_device = render_device.wgpu_device();
+            
+            let imported = unsafe {
+                wgpu_device.as_hal::<VulkanApi, _, Result<ImportedImage>>(|hal_device| {
+                    let Some(hal_device) = hal_device else {
+                        libc::close(fd);
+                        return Err(ExternalSurfaceError::UnsupportedBackend(
+                            "Vulkan backend required for external memory".into(),
+                        ));
+                    };
+                    
+                    import_image_from_fd(hal_device, fd, size, vk_format)
+                })
+            }?;
+            let vk_image = imported.image;
+            let (hal_usage, usage) = shared_texture_uses(format);
+            
+            let hal_desc = wgpu_hal::TextureDescriptor {
+                label: Some("imported_external_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: hal_usage,
+                memory_flags: wgpu_hal::MemoryFlags::empty(),
+                view_formats: vec![],
+            };
+            
+            // wgpu-hal calls the drop callback once the texture is destroyed, which is
+            // the point where the image and the imported memory can be released
+            let hal_texture = unsafe {
+                wgpu_hal::vulkan::Device::texture_from_raw(
+                    vk_image,
+                    &hal_desc,
+                    Some(Box::new(move || drop(imported))),
+                )
+            };
+            
+            let texture_desc = TextureDescriptor {
+                label: Some("imported_external_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            };
+            
+            let texture = unsafe {
+                wgpu_device.create_texture_from_hal::<VulkanApi>(hal_texture, &texture_desc)
+            };
+            
+            Ok(Self {
+                texture: Arc::new(texture.into()),
+                // The fd now belongs to the driver and must not be used again
+                memory_handle: None,
+                semaphore_handle: None,
+                size,
+                format,
+            })
+        }
     }
     
     fn export_memory_handle(
-        render_device: &RenderDevice,
-        texture: &wgpu::Texture,
+        _render_device: &RenderDevice,
+        _texture: &wgpu::Texture,
     ) -> Result<Option<ExternalMemoryHandle>> {
         // This would use ash to export the memory handle
         // For now, return None as a placeholder
@@ -158,6 +211,320 @@ impl VulkanExternalTexture {
     }
 }
 
+// Usage flags shared images are created with on both sides. Opaque fd imports
+// require the importing image to match the exporting one exactly. Both are also
+// copied from: depth images when Bevy runs a depth prepass, color images by
+// consumers copying frames out of the shared buffers.
+pub(crate) fn shared_image_usage(format: vk::Format) -> vk::ImageUsageFlags {
+    if vk_format_to_wgpu(format).is_some_and(|format| format.is_depth_stencil_format()) {
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC
+    } else {
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC
+    }
+}
+
+// The wgpu side of `shared_image_usage`, for wrapping shared images
+pub(crate) fn shared_texture_uses(format: TextureFormat) -> (wgpu_hal::TextureUses, TextureUsages) {
+    if format.is_depth_stencil_format() {
+        (
+            wgpu_hal::TextureUses::DEPTH_STENCIL_READ
+                | wgpu_hal::TextureUses::DEPTH_STENCIL_WRITE
+                | wgpu_hal::TextureUses::RESOURCE
+                | wgpu_hal::TextureUses::COPY_SRC,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+        )
+    } else {
+        (
+            wgpu_hal::TextureUses::COLOR_TARGET | wgpu_hal::TextureUses::RESOURCE | wgpu_hal::TextureUses::COPY_SRC,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+        )
+    }
+}
+
+pub(crate) fn require_device_extension(
+    hal_device: &wgpu_hal::vulkan::Device,
+    extension: &CStr,
+) -> Result<()> {
+    if hal_device.enabled_device_extensions().contains(&extension) {
+        Ok(())
+    } else {
+        Err(ExternalSurfaceError::VulkanExtensionNotAvailable(
+            extension.to_string_lossy().into_owned(),
+        ))
+    }
+}
+
+// Image and memory imported from another process, released together once the
+// wgpu texture wrapping the image is destroyed
+#[cfg(unix)]
+pub(crate) struct ImportedImage {
+    device: ash::Device,
+    pub(crate) image: vk::Image,
+    memory: vk::DeviceMemory,
+}
+
+#[cfg(unix)]
+impl Drop for ImportedImage {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+// Takes ownership of `fd`: it is either consumed by the driver or closed here
+#[cfg(unix)]
+unsafe fn import_image_from_fd(
+    hal_device: &wgpu_hal::vulkan::Device,
+    fd: RawFd,
+    size: Extent3d,
+    format: vk::Format,
+) -> Result<ImportedImage> {
+    if let Err(e) = require_device_extension(hal_device, ash::khr::external_memory_fd::NAME) {
+        unsafe { libc::close(fd) };
+        return Err(e);
+    }
+    
+    let instance = hal_device.shared_instance().raw_instance();
+    let mem_properties = unsafe {
+        instance.get_physical_device_memory_properties(hal_device.raw_physical_device())
+    };
+    
+    unsafe { import_image_memory_fd(hal_device.raw_device(), &mem_properties, fd, size, format) }
+}
+
+// Creates an image matching the producer's and imports `fd` as its dedicated
+// memory. Takes ownership of `fd` like `import_image_from_fd`.
+#[cfg(unix)]
+pub(crate) unsafe fn import_image_memory_fd(
+    device: &ash::Device,
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+    fd: RawFd,
+    size: Extent3d,
+    format: vk::Format,
+) -> Result<ImportedImage> {
+    let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
+        .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+    
+    let image_info = shared_image_info(size, format)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .push_next(&mut external_memory_info);
+    
+    let image = match unsafe { device.create_image(&image_info, None) } {
+        Ok(image) => image,
+        Err(e) => {
+            unsafe { libc::close(fd) };
+            return Err(ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to create image: {:?}", e)));
+        }
+    };
+    
+    unsafe { bind_imported_memory(device, mem_properties, image, fd, vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD, !0) }
+}
+
+// Creates an image with the exact DRM format modifier and plane layout of a
+// DMA-BUF and imports it. Takes ownership of `fd` like `import_image_from_fd`.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn import_dma_buf_image(
+    device: &ash::Device,
+    ext_memory_fd: &ash::khr::external_memory_fd::Device,
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+    fd: RawFd,
+    size: Extent3d,
+    format: vk::Format,
+    modifier: u64,
+    planes: &[vk::SubresourceLayout],
+) -> Result<ImportedImage> {
+    let mut modifier_info = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+        .drm_format_modifier(modifier)
+        .plane_layouts(planes);
+    let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
+        .handle_types(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+    
+    let image_info = shared_image_info(size, format)
+        .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+        .push_next(&mut modifier_info)
+        .push_next(&mut external_memory_info);
+    
+    let image = match unsafe { device.create_image(&image_info, None) } {
+        Ok(image) => image,
+        Err(e) => {
+            unsafe { libc::close(fd) };
+            return Err(ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to create image: {:?}", e)));
+        }
+    };
+    
+    // Unlike opaque fds, a DMA-BUF restricts the memory types it can be imported as
+    let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+    let queried = unsafe {
+        ext_memory_fd.get_memory_fd_properties(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT, fd, &mut fd_properties)
+    };
+    if let Err(e) = queried {
+        unsafe {
+            device.destroy_image(image, None);
+            libc::close(fd);
+        }
+        return Err(ExternalSurfaceError::MemoryExportFailed(format!("Failed to query DMA-BUF properties: {:?}", e)));
+    }
+    
+    unsafe {
+        bind_imported_memory(
+            device,
+            mem_properties,
+            image,
+            fd,
+            vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+            fd_properties.memory_type_bits,
+        )
+    }
+}
+
+// Everything but the tiling, which depends on how the memory is shared
+#[cfg(unix)]
+fn shared_image_info<'a>(size: Extent3d, format: vk::Format) -> vk::ImageCreateInfo<'a> {
+    vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D {
+            width: size.width,
+            height: size.height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .usage(shared_image_usage(format))
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+}
+
+// Imports `fd` as the dedicated memory of `image` and binds it. Takes ownership of
+// both: on failure the image is destroyed and the fd closed.
+#[cfg(unix)]
+unsafe fn bind_imported_memory(
+    device: &ash::Device,
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+    image: vk::Image,
+    fd: RawFd,
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+    allowed_memory_types: u32,
+) -> Result<ImportedImage> {
+    let fail = |e: ExternalSurfaceError| {
+        unsafe {
+            device.destroy_image(image, None);
+            libc::close(fd);
+        }
+        e
+    };
+    
+    let mem_reqs = unsafe { device.get_image_memory_requirements(image) };
+    
+    let memory_type_index = find_memory_type(
+        mem_properties,
+        mem_reqs.memory_type_bits & allowed_memory_types,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )
+    .map_err(fail)?;
+    
+    // The producer allocates dedicated memory, and imports must match that
+    let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+    let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+        .handle_type(handle_type)
+        .fd(fd);
+    
+    let alloc_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(mem_reqs.size)
+        .memory_type_index(memory_type_index)
+        .push_next(&mut import_info)
+        .push_next(&mut dedicated_info);
+    
+    let memory = unsafe { device.allocate_memory(&alloc_info, None) }
+        .map_err(|e| fail(ExternalSurfaceError::MemoryExportFailed(format!("Failed to import memory: {:?}", e))))?;
+    
+    // The driver owns the fd from here on
+    let imported = ImportedImage {
+        device: device.clone(),
+        image,
+        memory,
+    };
+    
+    unsafe { device.bind_image_memory(image, memory, 0) }
+        .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to bind memory: {:?}", e)))?;
+    
+    Ok(imported)
+}
+
+// Uncompressed formats with the same meaning in both APIs. Depth24Plus,
+// Depth24PlusStencil8 and Stencil8 are missing on purpose: wgpu picks their Vulkan
+// format depending on what the driver supports.
+const FORMAT_TABLE: &[(vk::Format, TextureFormat)] = &[
+    // 8 bit per channel
+    (vk::Format::R8_UNORM, TextureFormat::R8Unorm),
+    (vk::Format::R8_SNORM, TextureFormat::R8Snorm),
+    (vk::Format::R8_UINT, TextureFormat::R8Uint),
+    (vk::Format::R8_SINT, TextureFormat::R8Sint),
+    (vk::Format::R8G8_UNORM, TextureFormat::Rg8Unorm),
+    (vk::Format::R8G8_SNORM, TextureFormat::Rg8Snorm),
+    (vk::Format::R8G8_UINT, TextureFormat::Rg8Uint),
+    (vk::Format::R8G8_SINT, TextureFormat::Rg8Sint),
+    (vk::Format::R8G8B8A8_UNORM, TextureFormat::Rgba8Unorm),
+    (vk::Format::R8G8B8A8_SRGB, TextureFormat::Rgba8UnormSrgb),
+    (vk::Format::R8G8B8A8_SNORM, TextureFormat::Rgba8Snorm),
+    (vk::Format::R8G8B8A8_UINT, TextureFormat::Rgba8Uint),
+    (vk::Format::R8G8B8A8_SINT, TextureFormat::Rgba8Sint),
+    (vk::Format::B8G8R8A8_UNORM, TextureFormat::Bgra8Unorm),
+    (vk::Format::B8G8R8A8_SRGB, TextureFormat::Bgra8UnormSrgb),
+    // 16 bit per channel
+    (vk::Format::R16_UNORM, TextureFormat::R16Unorm),
+    (vk::Format::R16_SNORM, TextureFormat::R16Snorm),
+    (vk::Format::R16_UINT, TextureFormat::R16Uint),
+    (vk::Format::R16_SINT, TextureFormat::R16Sint),
+    (vk::Format::R16_SFLOAT, TextureFormat::R16Float),
+    (vk::Format::R16G16_UNORM, TextureFormat::Rg16Unorm),
+    (vk::Format::R16G16_SNORM, TextureFormat::Rg16Snorm),
+    (vk::Format::R16G16_UINT, TextureFormat::Rg16Uint),
+    (vk::Format::R16G16_SINT, TextureFormat::Rg16Sint),
+    (vk::Format::R16G16_SFLOAT, TextureFormat::Rg16Float),
+    (vk::Format::R16G16B16A16_UNORM, TextureFormat::Rgba16Unorm),
+    (vk::Format::R16G16B16A16_SNORM, TextureFormat::Rgba16Snorm),
+    (vk::Format::R16G16B16A16_UINT, TextureFormat::Rgba16Uint),
+    (vk::Format::R16G16B16A16_SINT, TextureFormat::Rgba16Sint),
+    (vk::Format::R16G16B16A16_SFLOAT, TextureFormat::Rgba16Float),
+    // 32 and 64 bit per channel
+    (vk::Format::R32_UINT, TextureFormat::R32Uint),
+    (vk::Format::R32_SINT, TextureFormat::R32Sint),
+    (vk::Format::R32_SFLOAT, TextureFormat::R32Float),
+    (vk::Format::R32G32_UINT, TextureFormat::Rg32Uint),
+    (vk::Format::R32G32_SINT, TextureFormat::Rg32Sint),
+    (vk::Format::R32G32_SFLOAT, TextureFormat::Rg32Float),
+    (vk::Format::R32G32B32A32_UINT, TextureFormat::Rgba32Uint),
+    (vk::Format::R32G32B32A32_SINT, TextureFormat::Rgba32Sint),
+    (vk::Format::R32G32B32A32_SFLOAT, TextureFormat::Rgba32Float),
+    (vk::Format::R64_UINT, TextureFormat::R64Uint),
+    // Packed
+    (vk::Format::A2B10G10R10_UNORM_PACK32, TextureFormat::Rgb10a2Unorm),
+    (vk::Format::A2B10G10R10_UINT_PACK32, TextureFormat::Rgb10a2Uint),
+    (vk::Format::B10G11R11_UFLOAT_PACK32, TextureFormat::Rg11b10Ufloat),
+    (vk::Format::E5B9G9R9_UFLOAT_PACK32, TextureFormat::Rgb9e5Ufloat),
+    // Depth and stencil
+    (vk::Format::D16_UNORM, TextureFormat::Depth16Unorm),
+    (vk::Format::D32_SFLOAT, TextureFormat::Depth32Float),
+    (vk::Format::D32_SFLOAT_S8_UINT, TextureFormat::Depth32FloatStencil8),
+];
+
+pub fn vk_format_to_wgpu(format: vk::Format) -> Option<TextureFormat> {
+    FORMAT_TABLE.iter()
+        .find(|(vk_format, _)| *vk_format == format)
+        .map(|&(_, wgpu_format)| wgpu_format)
+}
+
+pub fn wgpu_format_to_vk(format: TextureFormat) -> Option<vk::Format> {
+    FORMAT_TABLE.iter()
+        .find(|(_, wgpu_format)| *wgpu_format == format)
+        .map(|&(vk_format, _)| vk_format)
+}
+
 pub struct VulkanInteropPlugin;
 
 impl Plugin for VulkanInteropPlugin {
@@ -182,29 +549,337 @@ fn check_vulkan_features(render_device: Res<RenderDevice>) {
 }
 
 // Helper for creating synchronization primitives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemaphoreKind {
+    Binary,
+    Timeline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemaphoreHandleType {
+    // Reference to the semaphore itself, exported once and shared for its lifetime
+    OpaqueFd,
+    // Linux sync_file holding a single pending signal, binary semaphores only
+    SyncFd,
+}
+
+impl SemaphoreHandleType {
+    fn as_vk(self) -> vk::ExternalSemaphoreHandleTypeFlags {
+        match self {
+            SemaphoreHandleType::OpaqueFd => vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD,
+            SemaphoreHandleType::SyncFd => vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD,
+        }
+    }
+}
+
+// A VkSemaphore shared with another process. Waits and signals are submitted to
+// the VkQueue wgpu renders with, between wgpu's own submissions, and timeline
+// semaphores can be signalled and waited on from the host as well.
 pub struct ExternalSemaphore {
-    #[cfg(unix)]
-    fd: Option<i32>,
-    #[cfg(windows)]
-    handle: Option<isize>,
+    raw: Arc<RawSemaphore>,
+    queue: vk::Queue,
+    kind: SemaphoreKind,
+    handle_type: SemaphoreHandleType,
+    ext_semaphore_fd: ash::khr::external_semaphore_fd::Device,
 }
 
 impl ExternalSemaphore {
-    pub fn create_exportable(render_device: &RenderDevice) -> Result<Self> {
-        // Placeholder implementation
+    pub fn create_exportable(
+        render_device: &RenderDevice,
+        kind: SemaphoreKind,
+        handle_type: SemaphoreHandleType,
+    ) -> Result<Self> {
+        with_hal_device(render_device, |hal_device| unsafe {
+            Self::from_hal_device(hal_device, kind, Some(handle_type))
+        })
+    }
+    
+    // Creates a semaphore and moves the payload of `fd` into it. Ownership of `fd`
+    // passes to this function in all cases.
+    #[cfg(unix)]
+    pub fn import_fd(
+        render_device: &RenderDevice,
+        fd: RawFd,
+        kind: SemaphoreKind,
+        handle_type: SemaphoreHandleType,
+    ) -> Result<Self> {
+        let semaphore = with_hal_device(render_device, |hal_device| unsafe {
+            Self::from_hal_device(hal_device, kind, None)
+        });
+        
+        match semaphore {
+            Ok(semaphore) => {
+                semaphore.import_payload_fd(fd, handle_type)?;
+                Ok(semaphore)
+            }
+            Err(e) => {
+                unsafe { libc::close(fd) };
+                Err(e)
+            }
+        }
+    }
+    
+    pub(crate) unsafe fn from_hal_device(
+        hal_device: &wgpu_hal::vulkan::Device,
+        kind: SemaphoreKind,
+        export_type: Option<SemaphoreHandleType>,
+    ) -> Result<Self> {
+        require_device_extension(hal_device, ash::khr::external_semaphore_fd::NAME)?;
+        
+        if kind == SemaphoreKind::Timeline && export_type == Some(SemaphoreHandleType::SyncFd) {
+            return Err(ExternalSurfaceError::SynchronizationFailed(
+                "Timeline semaphores cannot be exported as sync fds".into(),
+            ));
+        }
+        
+        let device = hal_device.raw_device();
+        let instance = hal_device.shared_instance().raw_instance();
+        
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(match kind {
+                SemaphoreKind::Binary => vk::SemaphoreType::BINARY,
+                SemaphoreKind::Timeline => vk::SemaphoreType::TIMELINE,
+            })
+            .initial_value(0);
+        let mut export_info = vk::ExportSemaphoreCreateInfo::default()
+            .handle_types(export_type.map_or(vk::ExternalSemaphoreHandleTypeFlags::empty(), SemaphoreHandleType::as_vk));
+        
+        let mut create_info = vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+        if export_type.is_some() {
+            create_info = create_info.push_next(&mut export_info);
+        }
+        
+        let semaphore = unsafe { device.create_semaphore(&create_info, None) }
+            .map_err(|e| ExternalSurfaceError::SynchronizationFailed(format!("Failed to create semaphore: {:?}", e)))?;
+        
         Ok(Self {
-            #[cfg(unix)]
-            fd: None,
-            #[cfg(windows)]
-            handle: None,
+            raw: Arc::new(RawSemaphore { device: device.clone(), semaphore }),
+            queue: hal_device.raw_queue(),
+            kind,
+            handle_type: export_type.unwrap_or(SemaphoreHandleType::OpaqueFd),
+            ext_semaphore_fd: ash::khr::external_semaphore_fd::Device::new(instance, device),
         })
     }
     
-    pub fn signal(&self, render_queue: &RenderQueue) {
-        // Would submit a signal operation to the queue
+    pub fn raw(&self) -> vk::Semaphore {
+        self.raw.semaphore
+    }
+    
+    pub fn kind(&self) -> SemaphoreKind {
+        self.kind
     }
     
-    pub fn wait(&self, render_queue: &RenderQueue) {
-        // Would submit a wait operation to the queue
+    // Exports a new fd for the semaphore. The caller owns the returned fd.
+    // Exporting a sync fd requires a pending signal and unsignals the semaphore.
+    #[cfg(unix)]
+    pub fn export_fd(&self) -> Result<RawFd> {
+        let fd_info = vk::SemaphoreGetFdInfoKHR::default()
+            .semaphore(self.raw.semaphore)
+            .handle_type(self.handle_type.as_vk());
+        
+        unsafe { self.ext_semaphore_fd.get_semaphore_fd(&fd_info) }
+            .map_err(|e| ExternalSurfaceError::SynchronizationFailed(format!("Failed to export semaphore fd: {:?}", e)))
     }
-}
\ No newline at end of file
+    
+    // Replaces the payload with the one referenced by `fd`, taking ownership of it.
+    // Sync fds are imported temporarily and only apply to the next wait.
+    #[cfg(unix)]
+    pub fn import_payload_fd(&self, fd: RawFd, handle_type: SemaphoreHandleType) -> Result<()> {
+        let flags = match handle_type {
+            SemaphoreHandleType::OpaqueFd => vk::SemaphoreImportFlags::empty(),
+            SemaphoreHandleType::SyncFd => vk::SemaphoreImportFlags::TEMPORARY,
+        };
+        
+        let import_info = vk::ImportSemaphoreFdInfoKHR::default()
+            .semaphore(self.raw.semaphore)
+            .flags(flags)
+            .handle_type(handle_type.as_vk())
+            .fd(fd);
+        
+        unsafe { self.ext_semaphore_fd.import_semaphore_fd(&import_info) }.map_err(|e| {
+            unsafe { libc::close(fd) };
+            ExternalSurfaceError::SynchronizationFailed(format!("Failed to import semaphore fd: {:?}", e))
+        })
+    }
+    
+    // Submits a signal of the semaphore, to `value` for timeline semaphores, once
+    // all work submitted to the render queue so far has completed. Binary
+    // semaphores must be unsignaled with no other signal pending.
+    //
+    // Unsafe because nothing else may use the render queue while this runs: call it
+    // from an exclusive render world system, and never submit to the queue from the
+    // main world while the semaphore is in use.
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn queue_signal(&self, render_queue: &RenderQueue, value: u64) -> Result<()> {
+        // Flushes wgpu's pending work so the signal covers it
+        render_queue.submit(std::iter::empty());
+        
+        let signal = [(self.raw.semaphore, self.timeline_value(value))];
+        unsafe { submit_semaphores(&self.raw.device, self.queue, &[], &signal) }?;
+        self.keep_alive(render_queue);
+        
+        Ok(())
+    }
+    
+    // Submits a wait on the semaphore, for `value` with timeline semaphores, which
+    // all work submitted to the render queue afterwards waits for. A binary
+    // semaphore must have a signal submitted or imported beforehand, which the wait
+    // consumes.
+    //
+    // Unsafe for the same reason as `queue_signal`. The signal must eventually
+    // happen, a wait that never completes stalls the render queue for good.
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn queue_wait(&self, render_queue: &RenderQueue, value: u64) -> Result<()> {
+        let wait = [(self.raw.semaphore, self.timeline_value(value))];
+        unsafe { submit_semaphores(&self.raw.device, self.queue, &wait, &[]) }?;
+        self.keep_alive(render_queue);
+        
+        Ok(())
+    }
+    
+    fn timeline_value(&self, value: u64) -> Option<u64> {
+        (self.kind == SemaphoreKind::Timeline).then_some(value)
+    }
+    
+    // Keeps the semaphore alive until a submission following the raw one has
+    // completed, and with it the raw one
+    fn keep_alive(&self, render_queue: &RenderQueue) {
+        render_queue.submit(std::iter::empty());
+        
+        let raw = self.raw.clone();
+        render_queue.on_submitted_work_done(move || drop(raw));
+    }
+    
+    // Signals a timeline semaphore to `value` once all work submitted to the render
+    // queue so far has completed. wgpu reports completion when it next polls the
+    // device, usually during the following frame's submission. Binary semaphores
+    // can only be signalled by a queue submission, see `queue_signal`.
+    pub fn signal(&self, render_queue: &RenderQueue, value: u64) -> Result<()> {
+        if self.kind != SemaphoreKind::Timeline {
+            return Err(ExternalSurfaceError::SynchronizationFailed(
+                "Host signals require a timeline semaphore".into(),
+            ));
+        }
+        
+        // Keeps the semaphore alive until the callback has run
+        let raw = self.raw.clone();
+        render_queue.on_submitted_work_done(move || {
+            let signal_info = vk::SemaphoreSignalInfo::default()
+                .semaphore(raw.semaphore)
+                .value(value);
+            
+            if let Err(e) = unsafe { raw.device.signal_semaphore(&signal_info) } {
+                error!("Failed to signal semaphore to {}: {:?}", value, e);
+            }
+        });
+        
+        Ok(())
+    }
+    
+    // Blocks the calling thread until a timeline semaphore reaches `value`, failing
+    // if `timeout` expires first
+    pub fn wait(&self, value: u64, timeout: Duration) -> Result<()> {
+        if self.wait_for_value(value, timeout)? {
+            Ok(())
+        } else {
+            Err(ExternalSurfaceError::SynchronizationFailed(
+                format!("Timed out after {:?} waiting for semaphore value {}", timeout, value),
+            ))
+        }
+    }
+    
+    // Current counter value of a timeline semaphore
+    pub fn value(&self) -> Result<u64> {
+        unsafe { self.raw.device.get_semaphore_counter_value(self.raw.semaphore) }
+            .map_err(|e| ExternalSurfaceError::SynchronizationFailed(format!("Failed to query semaphore value: {:?}", e)))
+    }
+    
+    // Blocks the calling thread until a timeline semaphore reaches `value`. Returns
+    // false if `timeout` expired first. Nothing is submitted to the queue, so giving
+    // up on a peer that never signals is safe.
+    pub fn wait_for_value(&self, value: u64, timeout: Duration) -> Result<bool> {
+        if self.kind != SemaphoreKind::Timeline {
+            return Err(ExternalSurfaceError::SynchronizationFailed(
+                "Host waits require a timeline semaphore".into(),
+            ));
+        }
+        
+        let semaphores = [self.raw.semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(&semaphores)
+            .values(&values);
+        
+        match unsafe { self.raw.device.wait_semaphores(&wait_info, timeout.as_nanos() as u64) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(ExternalSurfaceError::SynchronizationFailed(format!("Failed to wait for semaphore value: {:?}", e))),
+        }
+    }
+}
+
+// Submits a batch without command buffers that waits on and signals the given
+// semaphores, with the value for timeline ones and `None` for binary ones. A wait
+// applies to everything submitted to `queue` afterwards and a signal covers
+// everything submitted before, so no commands are needed.
+//
+// `queue` must belong to `device` and nothing else may use it while this runs.
+// The semaphores must stay alive until the batch has completed.
+pub(crate) unsafe fn submit_semaphores(
+    device: &ash::Device,
+    queue: vk::Queue,
+    waits: &[(vk::Semaphore, Option<u64>)],
+    signals: &[(vk::Semaphore, Option<u64>)],
+) -> Result<()> {
+    let wait_semaphores: Vec<_> = waits.iter().map(|&(semaphore, _)| semaphore).collect();
+    let wait_values: Vec<_> = waits.iter().map(|&(_, value)| value.unwrap_or(0)).collect();
+    let wait_stages = vec![vk::PipelineStageFlags::ALL_COMMANDS; waits.len()];
+    let signal_semaphores: Vec<_> = signals.iter().map(|&(semaphore, _)| semaphore).collect();
+    let signal_values: Vec<_> = signals.iter().map(|&(_, value)| value.unwrap_or(0)).collect();
+    
+    let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
+        .wait_semaphore_values(&wait_values)
+        .signal_semaphore_values(&signal_values);
+    let mut submit_info = vk::SubmitInfo::default()
+        .wait_semaphores(&wait_semaphores)
+        .wait_dst_stage_mask(&wait_stages)
+        .signal_semaphores(&signal_semaphores);
+    // Binary semaphores ignore the values, which is only valid to chain with
+    // timeline semaphore support
+    if waits.iter().chain(signals).any(|(_, value)| value.is_some()) {
+        submit_info = submit_info.push_next(&mut timeline_info);
+    }
+    
+    unsafe { device.queue_submit(queue, &[submit_info], vk::Fence::null()) }
+        .map_err(|e| ExternalSurfaceError::SynchronizationFailed(format!("Failed to submit semaphore operations: {:?}", e)))
+}
+
+// The semaphore itself, shared with pending signals so it outlives them
+struct RawSemaphore {
+    device: ash::Device,
+    semaphore: vk::Semaphore,
+}
+
+impl Drop for RawSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
+
+// Runs `f` with the Vulkan HAL device behind `render_device`
+fn with_hal_device<R>(
+    render_device: &RenderDevice,
+    f: impl FnOnce(&wgpu_hal::vulkan::Device) -> Result<R>,
+) -> Result<R> {
+    unsafe {
+        render_device.wgpu_device().as_hal::<VulkanApi, _, Result<R>>(|hal_device| {
+            let hal_device = hal_device.ok_or_else(|| {
+                ExternalSurfaceError::UnsupportedBackend("Vulkan backend required for external semaphores".into())
+            })?;
+            
+            f(hal_device)
+        })
+    }
+}
@@ -1,31 +1,148 @@
 use bevy::{
     prelude::*,
+    core_pipeline::{
+        core_3d::{prepare_core_3d_depth_textures, Camera3dDepthLoadOp, CORE_3D_DEPTH_FORMAT},
+        tonemapping::Tonemapping,
+    },
     log::{info, warn, error},
     render::{
-        camera::{RenderTarget, ManualTextureView, ManualTextureViewHandle, ManualTextureViews},
-        renderer::RenderDevice,
+        camera::{
+            CameraProjection, CameraUpdateSystem, ExtractedCamera, NormalizedRenderTarget, RenderTarget,
+            ManualTextureView, ManualTextureViewHandle, ManualTextureViews,
+        },
+        renderer::{render_system, RenderDevice, RenderQueue},
         extract_resource::{ExtractResource, ExtractResourcePlugin},
-        Render, RenderApp, RenderSet, ExtractSchedule,
+        texture::CachedTexture,
+        view::ViewDepthTexture,
+        Render, RenderApp, RenderSet,
     },
 };
 use ash::{self, vk};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use wgpu_hal::api::Vulkan as VulkanApi;
 
 #[cfg(unix)]
-use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
+#[cfg(unix)]
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
 #[cfg(unix)]
-use nix::sys::socket::{self, ControlMessageOwned, MsgFlags, UnixAddr};
+use nix::sys::socket::{self, UnixAddr};
 
+use crate::vulkan_interop::{
+    require_device_extension, shared_image_usage, shared_texture_uses, vk_format_to_wgpu, ExternalSemaphore, SemaphoreHandleType,
+    SemaphoreKind,
+};
+use crate::texture_view_handles::{ManualTextureViewHandles, ManualTextureViewHandlesPlugin, ReservedHandles};
 use crate::{ExternalSurfaceError, Result};
 
+#[cfg(unix)]
+pub mod client;
+#[cfg(unix)]
+pub mod consumer;
+pub mod protocol;
+
+#[cfg(unix)]
+use protocol::{
+    send_message, BufferReleased, Capabilities, FrameReady, Hello, MessageKind, MessageReader, Reject, ResizeRequest,
+};
+use protocol::{DmaBufLayout, DmaBufPlane, MemoryHandleType, Metadata, ProjectionInfo, TonemappingOperator};
+
+// How long the producer waits for consumers to release a buffer before dropping
+// the ones still holding it as stalled
+const CONSUMER_WAIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long the producer polls consumer sockets for releases at a time
+#[cfg(unix)]
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Upper bound of `VulkanSharingConfig::buffer_count`
+pub const MAX_BUFFER_COUNT: u32 = 8;
+
+// Format of the shared depth images, Bevy's `CORE_3D_DEPTH_FORMAT`
+pub const SHARED_DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+// Connections the listening socket queues before they are accepted
+#[cfg(unix)]
+const MAX_PENDING_CONSUMERS: i32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum FrameSyncMode {
+    // Binary semaphores: every frame comes with a sync fd of its render_finished
+    // signal, and each consumer signals one consumer_ready semaphore per buffer,
+    // exported once with the metadata
+    #[default]
+    Binary,
+    // One timeline semaphore per direction, exported once with the metadata.
+    // Frames only carry the value to wait on, and a consumer that missed a frame
+    // simply waits on a later value.
+    Timeline,
+}
+
+// How `SharedVulkanResources::swap_buffers` picks the next buffer to render into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferSwapPolicy {
+    // Buffers are rendered in order, waiting for consumers to release the next
+    // one. Consumers see every frame, and a slow consumer slows the producer down.
+    // One that holds a buffer for longer than `CONSUMER_WAIT_TIMEOUT` is dropped.
+    #[default]
+    Fifo,
+    // Latest wins: the next buffer no consumer holds is rendered into, skipping
+    // held ones. With three or more buffers the producer rarely waits, and a slow
+    // consumer only sees the newest frames. With every buffer held it waits like
+    // FIFO.
+    Mailbox,
+    // Buffers are rendered in order without waiting for consumers. In timeline mode
+    // a consumer still reading a buffer may see it being overwritten. Binary mode
+    // never renders into a buffer a consumer holds, so it behaves like mailbox.
+    Immediate,
+}
+
+// How the shared images' memory is exported to consumers
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MemoryExportMode {
+    // Opaque fds with optimal tiling. Only Vulkan on the same device and driver
+    // can import them.
+    #[default]
+    OpaqueFd,
+    // DMA-BUFs with an explicit DRM format modifier, importable by EGL, Wayland
+    // compositors, GStreamer and VA-API. The driver picks among the modifiers it
+    // supports for the format, restricted to `allowed_modifiers` unless empty.
+    DmaBuf { allowed_modifiers: Vec<u64> },
+}
+
+// How consumers are to interpret the values in the shared images. Nothing is
+// converted, this only declares what the producer renders, so PQ for example needs
+// the scene to be encoded by a custom pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum ColorSpace {
+    // sRGB primaries and transfer function, what Bevy renders by default
+    #[default]
+    Srgb,
+    // sRGB primaries, linear values in 0..=1
+    Linear,
+    // BT.2020 primaries with the SMPTE ST 2084 transfer function (HDR10)
+    Pq,
+    // Extended linear sRGB, 1.0 being 80 nits, values beyond 0..=1 allowed
+    ScRgb,
+}
+
 #[derive(Debug, Clone)]
 pub struct VulkanSharingConfig {
     pub width: u32,
     pub height: u32,
     pub format: vk::Format,
     pub ipc_socket_path: Option<String>,
-    pub enable_double_buffering: bool,
+    // Number of shared images, 1..=MAX_BUFFER_COUNT
+    pub buffer_count: u32,
+    pub swap_policy: BufferSwapPolicy,
+    pub sync_mode: FrameSyncMode,
+    pub memory_export: MemoryExportMode,
+    pub color_space: ColorSpace,
+    // Also shares a depth image per buffer, which 3D cameras without MSAA render
+    // their depth into. Only with `MemoryExportMode::OpaqueFd`.
+    pub share_depth: bool,
 }
 
 impl Default for VulkanSharingConfig {
@@ -35,144 +152,496 @@ impl Default for VulkanSharingConfig {
             height: 1080,
             format: vk::Format::B8G8R8A8_SRGB,
             ipc_socket_path: Some("/tmp/bevy_vulkan_sharing.sock".to_string()),
-            enable_double_buffering: true,
+            buffer_count: 2,
+            swap_policy: BufferSwapPolicy::Fifo,
+            sync_mode: FrameSyncMode::Binary,
+            memory_export: MemoryExportMode::OpaqueFd,
+            color_space: ColorSpace::Srgb,
+            share_depth: false,
         }
     }
 }
 
-#[derive(Resource, Clone)]
+// Identifies one of the app's shared surfaces, one per `VulkanSharingPlugin`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SharedSurfaceId(pub u32);
+
+// Makes a camera render into the given shared surface. Cameras without it keep
+// their own target.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SharedSurfaceTarget(pub SharedSurfaceId);
+
+// Every shared surface of the app, in the order their plugins were added
+#[derive(Resource, Clone, Default)]
+pub struct SharedSurfaces {
+    pub surfaces: Vec<SharedVulkanResources>,
+}
+
+impl SharedSurfaces {
+    pub fn get(&self, id: SharedSurfaceId) -> Option<&SharedVulkanResources> {
+        self.surfaces.iter().find(|surface| surface.id == id)
+    }
+    
+    pub fn get_mut(&mut self, id: SharedSurfaceId) -> Option<&mut SharedVulkanResources> {
+        self.surfaces.iter_mut().find(|surface| surface.id == id)
+    }
+}
+
+impl ExtractResource for SharedSurfaces {
+    type Source = SharedSurfaces;
+    
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+#[derive(Clone)]
 pub struct SharedVulkanResources {
+    pub id: SharedSurfaceId,
     pub config: VulkanSharingConfig,
     pub texture_handles: Vec<ManualTextureViewHandle>,
-    pub vulkan_images: Vec<vk::Image>,
-    pub vulkan_memory: Vec<vk::DeviceMemory>,
-    pub memory_fds: Vec<RawFd>,
-    pub render_finished_semaphores: Vec<vk::Semaphore>,
-    pub consumer_ready_semaphores: Vec<vk::Semaphore>,
+    // One per buffer with `MemoryExportMode::DmaBuf`
+    pub dma_buf_layouts: Vec<DmaBufLayout>,
+    // One per buffer with `share_depth`
+    pub depth_textures: Vec<CachedTexture>,
+    // Buffer the cameras render into this frame. Only the main world advances it,
+    // the render world renders into and announces the copy extracted along with
+    // the camera targets.
     pub current_buffer_index: usize,
     pub ipc_handler: Option<Arc<Mutex<IPCHandler>>>,
-    // Store device handles for cleanup
-    device: Option<Arc<ash::Device>>,
+    // The Vulkan objects themselves. Copies of this struct, like the one extracted
+    // to the render world each frame, only hold handles to them.
+    images: Option<Arc<SharedImages>>,
+    sync: Option<Arc<SharedSync>>,
+    // Images replaced by a resize, kept until the render world's last frame using
+    // them has completed
+    retired_images: Arc<Mutex<Vec<RetiredImages>>>,
+    // Handles `texture_handles` are taken from, reserved during setup
+    reserved_handles: Option<Arc<ReservedHandles>>,
 }
 
 impl SharedVulkanResources {
-    fn new(config: VulkanSharingConfig) -> Self {
+    fn new(id: SharedSurfaceId, config: VulkanSharingConfig) -> Self {
         Self {
+            id,
             config,
             texture_handles: Vec::new(),
-            vulkan_images: Vec::new(),
-            vulkan_memory: Vec::new(),
-            memory_fds: Vec::new(),
-            render_finished_semaphores: Vec::new(),
-            consumer_ready_semaphores: Vec::new(),
+            dma_buf_layouts: Vec::new(),
+            depth_textures: Vec::new(),
             current_buffer_index: 0,
             ipc_handler: None,
-            device: None,
+            images: None,
+            sync: None,
+            retired_images: Arc::default(),
+            reserved_handles: None,
+        }
+    }
+    
+    // Makes `buffer_set` the shared textures, returning the images it replaces
+    fn install_buffers(
+        &mut self,
+        buffer_set: SharedBufferSet,
+        manual_texture_views: &mut ManualTextureViews,
+    ) -> Option<Arc<SharedImages>> {
+        let reserved_handles = self.reserved_handles.as_ref()
+            .expect("texture view handles are reserved before buffers are installed");
+        
+        self.texture_handles.clear();
+        for (handle, manual_view) in reserved_handles.handles().zip(buffer_set.views) {
+            manual_texture_views.insert(handle, manual_view);
+            self.texture_handles.push(handle);
         }
+        
+        self.dma_buf_layouts = buffer_set.dma_buf_layouts;
+        self.depth_textures = buffer_set.depth_textures;
+        
+        self.images.replace(Arc::new(buffer_set.images))
+    }
+    
+    pub fn vulkan_images(&self) -> Vec<vk::Image> {
+        self.images.iter().flat_map(|images| &images.color).map(|buffer| buffer.image).collect()
+    }
+    
+    // Owned by the surface, consumers receive their own copies
+    pub fn memory_fds(&self) -> Vec<RawFd> {
+        self.images.iter().flat_map(|images| &images.color).map(|buffer| buffer.memory_fd).collect()
+    }
+    
+    pub fn depth_images(&self) -> Vec<vk::Image> {
+        self.images.iter().flat_map(|images| &images.depth).map(|buffer| buffer.image).collect()
+    }
+    
+    // Whether the images and semaphores have been created
+    pub fn is_ready(&self) -> bool {
+        self.images.is_some() && self.sync.is_some()
+    }
+    
+    // Memory fds sent to consumers, color then depth
+    fn shared_memory_fds(&self) -> Vec<RawFd> {
+        self.images.iter()
+            .flat_map(|images| images.color.iter().chain(&images.depth))
+            .map(|buffer| buffer.memory_fd)
+            .collect()
     }
     
     pub fn get_current_texture_handle(&self) -> Option<ManualTextureViewHandle> {
         self.texture_handles.get(self.current_buffer_index).copied()
     }
     
+    // Moves on to the next buffer, see `advance_shared_buffers`
     pub fn swap_buffers(&mut self) {
-        if self.config.enable_double_buffering && self.texture_handles.len() > 1 {
-            self.current_buffer_index = (self.current_buffer_index + 1) % self.texture_handles.len();
+        let buffer_count = self.texture_handles.len();
+        if buffer_count < 2 {
+            return;
         }
+        
+        let next = (self.current_buffer_index + 1) % buffer_count;
+        self.current_buffer_index = match (self.config.swap_policy, self.config.sync_mode) {
+            (BufferSwapPolicy::Fifo, _) | (BufferSwapPolicy::Immediate, FrameSyncMode::Timeline) => next,
+            // With every buffer held, the oldest one is waited on like in FIFO
+            (BufferSwapPolicy::Mailbox | BufferSwapPolicy::Immediate, _) => self.next_free_buffer().unwrap_or(next),
+        };
+    }
+    
+    // The first buffer after the current one that no consumer holds
+    fn next_free_buffer(&self) -> Option<usize> {
+        let mut handler = self.ipc_handler.as_ref()?.lock().ok()?;
+        handler.receive_messages();
+        
+        let buffer_count = self.texture_handles.len();
+        (1..buffer_count)
+            .map(|offset| (self.current_buffer_index + offset) % buffer_count)
+            .find(|&buffer_index| handler.is_buffer_free(buffer_index))
     }
 }
 
-// Make it extractable
-impl ExtractResource for SharedVulkanResources {
-    type Source = SharedVulkanResources;
-    
-    fn extract_resource(source: &Self::Source) -> Self {
-        source.clone()
+// One shared image with its exported memory
+struct SharedBuffer {
+    device: ash::Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    memory_fd: RawFd,
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+            libc::close(self.memory_fd);
+        }
     }
 }
 
-// Cleanup on drop
-impl Drop for SharedVulkanResources {
+// The shared images of one size, freed once the last `SharedVulkanResources`
+// holding them is gone. Unless they were retired by a resize, the GPU may still be
+// reading them at that point, so wgpu is waited on first.
+struct SharedImages {
+    color: Vec<SharedBuffer>,
+    depth: Vec<SharedBuffer>,
+    // Set once no submitted work uses the images anymore, see `retire_shared_buffers`
+    idle: AtomicBool,
+    // Keeps the device alive until the buffers above are destroyed
+    render_device: RenderDevice,
+}
+
+impl Drop for SharedImages {
     fn drop(&mut self) {
-        if let Some(device) = &self.device {
-            unsafe {
-                for &semaphore in &self.render_finished_semaphores {
-                    device.destroy_semaphore(semaphore, None);
-                }
-                for &semaphore in &self.consumer_ready_semaphores {
-                    device.destroy_semaphore(semaphore, None);
-                }
-                for &memory in &self.vulkan_memory {
-                    device.free_memory(memory, None);
-                }
-                for &image in &self.vulkan_images {
-                    device.destroy_image(image, None);
-                }
-            }
+        if !*self.idle.get_mut() {
+            self.render_device.poll(wgpu::Maintain::Wait);
         }
     }
 }
 
-pub struct VulkanSharingPlugin {
-    pub config: VulkanSharingConfig,
+// Images replaced by a resize
+struct RetiredImages {
+    images: Arc<SharedImages>,
+    // Whether the render world is waiting for the frames using them to complete
+    watched: bool,
 }
 
-impl Default for VulkanSharingPlugin {
-    fn default() -> Self {
-        Self {
-            config: VulkanSharingConfig::default(),
+// Buffers allocated for one size, not yet handed to `SharedVulkanResources`
+struct SharedBufferSet {
+    images: SharedImages,
+    views: Vec<ManualTextureView>,
+    dma_buf_layouts: Vec<DmaBufLayout>,
+    depth_textures: Vec<CachedTexture>,
+}
+
+// Semaphores signalled for consumers, which live as long as the surface. Queue
+// operations on them keep them alive until they have completed.
+struct SharedSync {
+    // Set in `FrameSyncMode::Binary`. Exporting a frame's signal as a sync fd
+    // unsignals it again, so one semaphore serves every buffer.
+    render_finished: Option<ExternalSemaphore>,
+    // Set in `FrameSyncMode::Timeline`
+    timeline: Option<TimelineSync>,
+    // Keeps the device alive until the semaphores are destroyed
+    _render_device: RenderDevice,
+}
+
+// Shared render_finished timeline used in `FrameSyncMode::Timeline`. The frame
+// counter lives here rather than in `SharedVulkanResources` so every copy of it
+// observes the same value. Each consumer signals its own consumer_ready timeline,
+// see `ConsumerReadySync`.
+struct TimelineSync {
+    pub render_finished: ExternalSemaphore,
+    render_finished_fd: RawFd,
+    frame_value: AtomicU64,
+}
+
+impl TimelineSync {
+    unsafe fn new(hal_device: &wgpu_hal::vulkan::Device) -> Result<Self> {
+        let render_finished = unsafe { ExternalSemaphore::from_hal_device(
+            hal_device,
+            SemaphoreKind::Timeline,
+            Some(SemaphoreHandleType::OpaqueFd),
+        ) }?;
+        
+        // Opaque fds reference the semaphore itself, so one export is enough and
+        // can be sent to every consumer
+        let render_finished_fd = render_finished.export_fd()?;
+        
+        Ok(Self {
+            render_finished,
+            render_finished_fd,
+            frame_value: AtomicU64::new(0),
+        })
+    }
+    
+    // Signals the next frame value once the frame's rendering is done. Same
+    // requirements as `ExternalSemaphore::queue_signal`.
+    unsafe fn signal_frame(&self, render_queue: &RenderQueue) -> Result<u64> {
+        let value = self.frame_value.fetch_add(1, Ordering::Relaxed) + 1;
+        unsafe { self.render_finished.queue_signal(render_queue, value) }?;
+        
+        Ok(value)
+    }
+}
+
+impl Drop for TimelineSync {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.render_finished_fd);
         }
     }
 }
 
+// Sent when a consumer attaches to the IPC socket and has received the metadata
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ConsumerConnected {
+    pub surface: SharedSurfaceId,
+    pub consumer_id: u64,
+}
+
+// Sent when a consumer hangs up or a write to it fails
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ConsumerDisconnected {
+    pub surface: SharedSurfaceId,
+    pub consumer_id: u64,
+}
+
+// Reallocates a surface's shared textures at a new size. Sent by game code or, on
+// behalf of consumers, for every `ResizeRequest` they send. The last one sent for
+// a surface before `PostUpdate` wins.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResizeSharedSurface {
+    pub surface: SharedSurfaceId,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Adds one shared surface. Add it once per surface, each with its own id, config
+// and socket, and mark the cameras rendering into it with `SharedSurfaceTarget`.
+#[derive(Default)]
+pub struct VulkanSharingPlugin {
+    pub id: SharedSurfaceId,
+    pub config: VulkanSharingConfig,
+}
+
 impl Plugin for VulkanSharingPlugin {
     fn build(&self, app: &mut App) {
-        let resources = SharedVulkanResources::new(self.config.clone());
-        
-        app.insert_resource(resources.clone());
-        app.add_plugins(ExtractResourcePlugin::<SharedVulkanResources>::default());
+        if let Some(mut shared_surfaces) = app.world_mut().get_resource_mut::<SharedSurfaces>() {
+            if shared_surfaces.get(self.id).is_some() {
+                panic!("VulkanSharingPlugin added twice for {:?}", self.id);
+            }
+            
+            let resources = SharedVulkanResources::new(self.id, self.config.clone());
+            shared_surfaces.surfaces.push(resources);
+            
+            // The first instance registered the systems, which handle every surface
+            return;
+        }
         
-        // Add system to update camera targets in MainWorld
-        app.add_systems(ExtractSchedule, extract_shared_resources);
+        app.insert_resource(SharedSurfaces {
+            surfaces: vec![SharedVulkanResources::new(self.id, self.config.clone())],
+        });
+        // Shared with whatever else registers manual texture views
+        if !app.is_plugin_added::<ManualTextureViewHandlesPlugin>() {
+            app.add_plugins(ManualTextureViewHandlesPlugin);
+        }
+        app.add_plugins(ExtractResourcePlugin::<SharedSurfaces>::default());
+        app.add_event::<ConsumerConnected>();
+        app.add_event::<ConsumerDisconnected>();
+        app.add_event::<ResizeSharedSurface>();
         
-        let render_app = app.sub_app_mut(RenderApp);
+        // Setup runs in the main world: RenderDevice is available there once the
+        // RenderPlugin has finished, and cameras resolve ManualTextureViews there
+        app.add_systems(Startup, setup_vulkan_sharing);
         
-        render_app.insert_resource(resources);
+        // Consumers come and go while the app runs, connection changes are
+        // reported before game code runs
+        app.add_systems(PreUpdate, poll_consumer_connection);
         
-        // Setup will run in RenderApp's Startup after RenderDevice is available
-        render_app.add_systems(
-            bevy::app::Startup,
-            setup_vulkan_sharing,
+        // Resize, pick this frame's buffer and point cameras at it before their
+        // render targets are resolved
+        app.add_systems(
+            PostUpdate,
+            (resize_shared_surface, advance_shared_buffers, update_camera_targets)
+                .chain()
+                .before(CameraUpdateSystem),
         );
+        app.add_systems(PostUpdate, update_camera_metadata);
+        
+        let render_app = app.sub_app_mut(RenderApp);
         
         render_app.add_systems(
             Render,
             (
-                wait_for_consumer.in_set(RenderSet::PrepareResources),
-                signal_render_finished.in_set(RenderSet::Cleanup),
+                use_shared_depth_textures
+                    .in_set(RenderSet::PrepareResources)
+                    .after(prepare_core_3d_depth_textures),
+                // Exclusive, as nothing else may use the queue while they submit to it
+                wait_for_consumers.in_set(RenderSet::Render).before(render_system),
+                signal_render_finished.in_set(RenderSet::Render).after(render_system),
+                retire_shared_buffers.in_set(RenderSet::Cleanup),
             ),
         );
     }
+    
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+// Picks the buffer rendered into this frame. Changing `SharedSurfaces` every frame
+// also makes sure the render world sees the new index.
+fn advance_shared_buffers(mut shared_surfaces: ResMut<SharedSurfaces>) {
+    for shared_resources in shared_surfaces.surfaces.iter_mut() {
+        shared_resources.swap_buffers();
+    }
 }
 
-fn extract_shared_resources(
-    shared_resources: Res<SharedVulkanResources>,
-    mut cameras: Query<&mut Camera>,
+fn update_camera_targets(
+    shared_surfaces: Res<SharedSurfaces>,
+    mut cameras: Query<(&mut Camera, &SharedSurfaceTarget)>,
 ) {
-    // Update camera targets to point to our shared texture
-    if let Some(handle) = shared_resources.get_current_texture_handle() {
-        for mut camera in cameras.iter_mut() {
+    for (mut camera, &SharedSurfaceTarget(id)) in cameras.iter_mut() {
+        let Some(handle) = shared_surfaces.get(id).and_then(|surface| surface.get_current_texture_handle()) else {
+            continue;
+        };
+        
+        if !matches!(camera.target, RenderTarget::TextureView(current) if current == handle) {
             camera.target = RenderTarget::TextureView(handle);
         }
     }
 }
 
+// What consumers are told about the camera rendering into the shared texture
+struct CameraState {
+    hdr: bool,
+    tonemapping: TonemappingOperator,
+    projection: ProjectionInfo,
+}
+
+type CameraStateQuery<'w, 's> = Query<'w, 's, (
+    &'static Camera,
+    &'static SharedSurfaceTarget,
+    Option<&'static Tonemapping>,
+    Option<&'static Projection>,
+)>;
+
+// State of the camera that renders last into the surface's shared texture, with
+// its projection for the given target size
+fn camera_state(cameras: &CameraStateQuery, id: SharedSurfaceId, width: u32, height: u32) -> Option<CameraState> {
+    let (camera, _, tonemapping, projection) = cameras.iter()
+        .filter(|(camera, target, _, _)| camera.is_active && target.0 == id)
+        .max_by_key(|(camera, _, _, _)| camera.order)?;
+    
+    let tonemapping = match tonemapping.copied().unwrap_or_default() {
+        Tonemapping::None => TonemappingOperator::None,
+        Tonemapping::Reinhard => TonemappingOperator::Reinhard,
+        Tonemapping::ReinhardLuminance => TonemappingOperator::ReinhardLuminance,
+        Tonemapping::AcesFitted => TonemappingOperator::AcesFitted,
+        Tonemapping::AgX => TonemappingOperator::AgX,
+        Tonemapping::SomewhatBoringDisplayTransform => TonemappingOperator::SomewhatBoringDisplayTransform,
+        Tonemapping::TonyMcMapface => TonemappingOperator::TonyMcMapface,
+        Tonemapping::BlenderFilmic => TonemappingOperator::BlenderFilmic,
+    };
+    
+    // The camera's own projection may not have caught up with a resize yet
+    let projection = projection.map(|projection| {
+        let mut projection = projection.clone();
+        projection.update(width as f32, height as f32);
+        
+        let (near, far) = match &projection {
+            Projection::Perspective(perspective) => (perspective.near, f32::INFINITY),
+            Projection::Orthographic(orthographic) => (orthographic.near, orthographic.far),
+            Projection::Custom(_) => (0.0, projection.far()),
+        };
+        
+        ProjectionInfo {
+            near,
+            far,
+            clip_from_view: projection.get_clip_from_view().to_cols_array(),
+        }
+    });
+    
+    Some(CameraState {
+        hdr: camera.hdr,
+        tonemapping,
+        projection: projection.unwrap_or_default(),
+    })
+}
+
+// Keeps the camera state sent to consumers in line with the cameras
+fn update_camera_metadata(
+    shared_surfaces: Res<SharedSurfaces>,
+    cameras: CameraStateQuery,
+) {
+    for shared_resources in &shared_surfaces.surfaces {
+        let Some(handler) = &shared_resources.ipc_handler else {
+            continue;
+        };
+        let config = &shared_resources.config;
+        let Some(state) = camera_state(&cameras, shared_resources.id, config.width, config.height) else {
+            continue;
+        };
+        
+        if let Ok(mut handler) = handler.lock() {
+            handler.set_camera_state(state);
+        }
+    }
+}
+
 fn setup_vulkan_sharing(
     render_device: Res<RenderDevice>,
     mut manual_texture_views: ResMut<ManualTextureViews>,
-    mut shared_resources: ResMut<SharedVulkanResources>,
+    handle_allocator: Res<ManualTextureViewHandles>,
+    mut shared_surfaces: ResMut<SharedSurfaces>,
+) {
+    for shared_resources in shared_surfaces.surfaces.iter_mut() {
+        setup_shared_surface(&render_device, &mut manual_texture_views, &handle_allocator, shared_resources);
+    }
+}
+
+fn setup_shared_surface(
+    render_device: &RenderDevice,
+    manual_texture_views: &mut ManualTextureViews,
+    handle_allocator: &ManualTextureViewHandles,
+    shared_resources: &mut SharedVulkanResources,
 ) {
-    info!("Setting up Vulkan sharing with config: {:?}", shared_resources.config);
+    info!("Setting up Vulkan sharing for {:?} with config: {:?}", shared_resources.id, shared_resources.config);
     
     let wgpu_device = render_device.wgpu_device();
     
@@ -185,15 +654,16 @@ fn setup_vulkan_sharing(
             
             create_and_setup_resources(
                 hal_device,
-                &render_device,
-                &mut manual_texture_views,
-                &mut shared_resources,
+                render_device,
+                manual_texture_views,
+                handle_allocator,
+                shared_resources,
             )
         })
     };
     
     if let Err(e) = setup_result {
-        error!("Failed to setup Vulkan sharing: {}", e);
+        error!("Failed to setup Vulkan sharing for {:?}: {}", shared_resources.id, e);
         return;
     }
     
@@ -201,22 +671,39 @@ fn setup_vulkan_sharing(
     if let Some(ref socket_path) = shared_resources.config.ipc_socket_path {
         #[cfg(unix)]
         {
-            match IPCHandler::new_server(socket_path) {
-                Ok(mut handler) => {
+            // Sent to each consumer as it completes the handshake
+            let metadata = Metadata {
+                width: shared_resources.config.width,
+                height: shared_resources.config.height,
+                format: shared_resources.config.format.as_raw() as u32,
+                buffer_count: shared_resources.config.buffer_count,
+                sync_mode: shared_resources.config.sync_mode,
+                memory_handle_type: match shared_resources.config.memory_export {
+                    MemoryExportMode::OpaqueFd => MemoryHandleType::OpaqueFd,
+                    MemoryExportMode::DmaBuf { .. } => MemoryHandleType::DmaBuf,
+                },
+                dma_buf_layouts: shared_resources.dma_buf_layouts.clone(),
+                color_space: shared_resources.config.color_space,
+                // Kept up to date by update_camera_metadata
+                camera_hdr: false,
+                tonemapping: TonemappingOperator::None,
+                projection: ProjectionInfo::default(),
+                depth_format: shared_resources.config.share_depth.then_some(SHARED_DEPTH_FORMAT.as_raw() as u32),
+                // Filled in per consumer
+                consumer_id: 0,
+            };
+            
+            let mut shared_fds = shared_resources.shared_memory_fds();
+            shared_fds.extend(
+                shared_resources.sync.as_ref()
+                    .and_then(|sync| sync.timeline.as_ref())
+                    .map(|timeline| timeline.render_finished_fd),
+            );
+            
+            match IPCHandler::new_server(socket_path, metadata, shared_fds) {
+                Ok(handler) => {
                     info!("IPC server initialized at {}", socket_path);
                     
-                    // Send initial metadata
-                    let metadata = IPCMetadata {
-                        width: shared_resources.config.width,
-                        height: shared_resources.config.height,
-                        format: shared_resources.config.format.as_raw() as u32,
-                        memory_fds: shared_resources.memory_fds.clone(),
-                    };
-                    
-                    if let Err(e) = handler.send_initial_metadata(&metadata) {
-                        error!("Failed to send initial metadata: {}", e);
-                    }
-                    
                     shared_resources.ipc_handler = Some(Arc::new(Mutex::new(handler)));
                 }
                 Err(e) => {
@@ -227,86 +714,248 @@ fn setup_vulkan_sharing(
     }
 }
 
+// Reallocates the shared textures for the last `ResizeSharedSurface` and hands them
+// to consumers. The old ones stay alive until the GPU is done with them, see
+// `retire_shared_buffers`.
+fn resize_shared_surface(
+    mut resize_events: EventReader<ResizeSharedSurface>,
+    render_device: Res<RenderDevice>,
+    mut manual_texture_views: ResMut<ManualTextureViews>,
+    mut shared_surfaces: ResMut<SharedSurfaces>,
+    cameras: CameraStateQuery,
+) {
+    let mut resizes: Vec<ResizeSharedSurface> = Vec::new();
+    for event in resize_events.read() {
+        resizes.retain(|resize| resize.surface != event.surface);
+        resizes.push(*event);
+    }
+    
+    for ResizeSharedSurface { surface, width, height } in resizes {
+        let Some(shared_resources) = shared_surfaces.get_mut(surface) else {
+            warn!("Ignoring resize of unknown shared surface {:?}", surface);
+            continue;
+        };
+        
+        resize_surface(&render_device, &mut manual_texture_views, shared_resources, &cameras, width, height);
+    }
+}
+
+fn resize_surface(
+    render_device: &RenderDevice,
+    manual_texture_views: &mut ManualTextureViews,
+    shared_resources: &mut SharedVulkanResources,
+    cameras: &CameraStateQuery,
+    width: u32,
+    height: u32,
+) {
+    let config = &shared_resources.config;
+    if (width, height) == (config.width, config.height) || shared_resources.images.is_none() {
+        return;
+    }
+    if width == 0 || height == 0 {
+        warn!("Ignoring resize of shared textures to {}x{}", width, height);
+        return;
+    }
+    
+    let mut config = config.clone();
+    config.width = width;
+    config.height = height;
+    
+    let created = unsafe {
+        render_device.wgpu_device().as_hal::<VulkanApi, _, Result<SharedBufferSet>>(|hal_device| {
+            let hal_device = hal_device.ok_or_else(|| {
+                ExternalSurfaceError::UnsupportedBackend("Not using Vulkan backend".into())
+            })?;
+            
+            create_shared_buffers(hal_device, render_device, &config)
+        })
+    };
+    let buffer_set = match created {
+        Ok(buffer_set) => buffer_set,
+        Err(e) => {
+            error!("Failed to resize shared textures of {:?} to {}x{}: {}", shared_resources.id, width, height, e);
+            return;
+        }
+    };
+    
+    shared_resources.config = config;
+    
+    let retired = shared_resources.install_buffers(buffer_set, manual_texture_views);
+    if let Ok(mut retired_images) = shared_resources.retired_images.lock() {
+        retired_images.extend(retired.map(|images| RetiredImages { images, watched: false }));
+    }
+    
+    if let Some(handler) = &shared_resources.ipc_handler
+        && let Ok(mut handler) = handler.lock()
+    {
+        if let Some(state) = camera_state(cameras, shared_resources.id, width, height) {
+            handler.set_camera_state(state);
+        }
+        handler.resize(width, height, shared_resources.dma_buf_layouts.clone(), &shared_resources.shared_memory_fds());
+    }
+    
+    info!("Resized shared textures of {:?} to {}x{}", shared_resources.id, width, height);
+}
+
 unsafe fn create_and_setup_resources(
     hal_device: &wgpu_hal::vulkan::Device,
     render_device: &RenderDevice,
     manual_texture_views: &mut ManualTextureViews,
+    handle_allocator: &ManualTextureViewHandles,
     shared_resources: &mut SharedVulkanResources,
 ) -> Result<()> {
+    // wgpu does not enable these on its own, the device has to be created with them
+    require_device_extension(hal_device, ash::khr::external_memory_fd::NAME)?;
+    require_device_extension(hal_device, ash::khr::external_semaphore_fd::NAME)?;
+    
+    let buffer_count = shared_resources.config.buffer_count;
+    if !(1..=MAX_BUFFER_COUNT).contains(&buffer_count) {
+        return Err(ExternalSurfaceError::SurfaceCreationFailed(format!(
+            "buffer_count must be between 1 and {}, got {}",
+            MAX_BUFFER_COUNT,
+            buffer_count,
+        )));
+    }
+    
+    let buffer_set = unsafe { create_shared_buffers(hal_device, render_device, &shared_resources.config) }?;
+    
+    // Resizes keep the buffer count, so these last for the surface's lifetime
+    let reserved_handles = handle_allocator.reserve(buffer_count, manual_texture_views).ok_or_else(|| {
+        ExternalSurfaceError::SurfaceCreationFailed("No free manual texture view handles left".into())
+    })?;
+    shared_resources.reserved_handles = Some(Arc::new(reserved_handles));
+    shared_resources.install_buffers(buffer_set, manual_texture_views);
+    
+    // consumer_ready semaphores are created per consumer as it connects
+    let (render_finished, timeline) = match shared_resources.config.sync_mode {
+        FrameSyncMode::Binary => {
+            let render_finished = unsafe { ExternalSemaphore::from_hal_device(
+                hal_device,
+                SemaphoreKind::Binary,
+                Some(SemaphoreHandleType::SyncFd),
+            ) }?;
+            (Some(render_finished), None)
+        }
+        FrameSyncMode::Timeline => (None, Some(unsafe { TimelineSync::new(hal_device) }?)),
+    };
+    shared_resources.sync = Some(Arc::new(SharedSync {
+        render_finished,
+        timeline,
+        _render_device: render_device.clone(),
+    }));
+    
+    info!("Successfully created {} shared textures and semaphores", buffer_count);
+    
+    Ok(())
+}
+
+// Allocates the exportable images for the config's size. Nothing is registered
+// until they have all been created.
+unsafe fn create_shared_buffers(
+    hal_device: &wgpu_hal::vulkan::Device,
+    render_device: &RenderDevice,
+    config: &VulkanSharingConfig,
+) -> Result<SharedBufferSet> {
     let raw_device = hal_device.raw_device();
     let raw_instance = hal_device.shared_instance().raw_instance();
     let physical_device = hal_device.raw_physical_device();
     
-    // Store device for cleanup
-    shared_resources.device = Some(Arc::new(raw_device.clone()));
+    let format = unsafe { validate_shared_format(raw_instance, physical_device, render_device, config) }?;
+    if config.share_depth {
+        if config.memory_export != MemoryExportMode::OpaqueFd {
+            return Err(ExternalSurfaceError::SurfaceCreationFailed(
+                "share_depth requires MemoryExportMode::OpaqueFd".into(),
+            ));
+        }
+        
+        unsafe { check_opaque_fd_export(raw_instance, physical_device, SHARED_DEPTH_FORMAT, config.width, config.height) }
+            .map_err(|reason| ExternalSurfaceError::SurfaceCreationFailed(format!("Cannot share depth: {}", reason)))?;
+    }
+    
+    // Modifiers the images may be created with, and their plane counts
+    let drm_modifiers = match &config.memory_export {
+        MemoryExportMode::OpaqueFd => None,
+        MemoryExportMode::DmaBuf { allowed_modifiers } => {
+            require_device_extension(hal_device, ash::ext::external_memory_dma_buf::NAME)?;
+            require_device_extension(hal_device, ash::ext::image_drm_format_modifier::NAME)?;
+            
+            Some(unsafe { supported_drm_modifiers(
+                raw_instance,
+                physical_device,
+                config,
+                allowed_modifiers,
+            ) }?)
+        }
+    };
+    let modifier_list: Option<Vec<u64>> = drm_modifiers.as_ref()
+        .map(|modifiers| modifiers.iter().map(|m| m.drm_format_modifier).collect());
+    let ext_drm_modifier = ash::ext::image_drm_format_modifier::Device::new(raw_instance, raw_device);
     
     // Load extension functions
-    let ext_memory_fd = ash::khr::external_memory_fd::Device::new(&raw_instance, &raw_device);
-    let ext_semaphore_fd = ash::khr::external_semaphore_fd::Device::new(&raw_instance, &raw_device);
+    let ext_memory_fd = ash::khr::external_memory_fd::Device::new(raw_instance, raw_device);
     
     // Query memory properties
     let mem_properties = unsafe { raw_instance.get_physical_device_memory_properties(physical_device) };
     
-    let buffer_count = if shared_resources.config.enable_double_buffering { 2 } else { 1 };
+    let mut buffer_set = SharedBufferSet {
+        images: SharedImages {
+            color: Vec::new(),
+            depth: Vec::new(),
+            idle: AtomicBool::new(false),
+            render_device: render_device.clone(),
+        },
+        views: Vec::new(),
+        dma_buf_layouts: Vec::new(),
+        depth_textures: Vec::new(),
+    };
     
-    for i in 0..buffer_count {
+    for i in 0..config.buffer_count {
         // Create exportable image
         let (vk_image, vk_memory, memory_fd) = unsafe { create_exportable_image_with_memory(
-            &raw_device,
+            raw_device,
             &ext_memory_fd,
             &mem_properties,
-            shared_resources.config.width,
-            shared_resources.config.height,
-            shared_resources.config.format,
+            config.width,
+            config.height,
+            config.format,
+            modifier_list.as_deref(),
         ) }?;
         
-        // Create HAL texture descriptor
-        let label = format!("shared_texture_{}", i);
-        let hal_desc = wgpu_hal::TextureDescriptor {
-            label: Some(&label),
-            size: wgpu::Extent3d {
-                width: shared_resources.config.width,
-                height: shared_resources.config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: convert_vk_format_to_wgpu(shared_resources.config.format),
-            usage: wgpu_hal::TextureUses::COLOR_TARGET | wgpu_hal::TextureUses::RESOURCE,
-            memory_flags: wgpu_hal::MemoryFlags::empty(),
-            view_formats: vec![],
-        };
+        // Owned from here on, so it is freed on error too
+        buffer_set.images.color.push(SharedBuffer {
+            device: raw_device.clone(),
+            image: vk_image,
+            memory: vk_memory,
+            memory_fd,
+        });
         
-        // Wrap the VkImage into a HAL texture
-        // texture_from_raw might be an associated function in this version
-        let hal_texture = unsafe {
-            wgpu_hal::vulkan::Device::texture_from_raw(
+        if let Some(modifiers) = &drm_modifiers {
+            let layout = unsafe { dma_buf_layout(
+                raw_device,
+                &ext_drm_modifier,
                 vk_image,
-                &hal_desc,
-                Some(Box::new(|| {})), // No-op drop callback - we manage lifetime
-            )
-        };
-        
-        // Convert HAL texture to wgpu texture
-        let wgpu_label = format!("shared_wgpu_texture_{}", i);
-        let wgpu_desc = wgpu::TextureDescriptor {
-            label: Some(&wgpu_label),
-            size: wgpu::Extent3d {
-                width: shared_resources.config.width,
-                height: shared_resources.config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: convert_vk_format_to_wgpu(shared_resources.config.format),
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        };
+                config.format,
+                modifiers,
+            ) }?;
+            
+            info!(
+                "Shared texture {} exported as DMA-BUF with modifier {:#x} ({} planes)",
+                i,
+                layout.modifier,
+                layout.planes.len(),
+            );
+            
+            buffer_set.dma_buf_layouts.push(layout);
+        }
         
-        let wgpu_texture = render_device.wgpu_device()
-            .create_texture_from_hal::<VulkanApi>(hal_texture, &wgpu_desc);
+        let wgpu_texture = unsafe { wrap_shared_image(
+            render_device,
+            vk_image,
+            config.width,
+            config.height,
+            format,
+            &format!("shared_texture_{}", i),
+        ) };
         
         // Create texture view
         let texture_view = wgpu_texture.create_view(&wgpu::TextureViewDescriptor {
@@ -314,45 +963,148 @@ unsafe fn create_and_setup_resources(
             ..Default::default()
         });
         
-        // Create ManualTextureView
-        let manual_view = ManualTextureView {
+        buffer_set.views.push(ManualTextureView {
             texture_view: texture_view.into(),
-            size: bevy::math::UVec2::new(
-                shared_resources.config.width,
-                shared_resources.config.height,
-            ),
-            format: convert_vk_format_to_wgpu(shared_resources.config.format),
-        };
-        
-        // Create a unique handle for this texture view
-        let handle = ManualTextureViewHandle(i as u32);
-        
-        // Insert the manual view with its handle
-        manual_texture_views.insert(handle, manual_view);
-        
-        // Store everything
-        shared_resources.texture_handles.push(handle);
-        shared_resources.vulkan_images.push(vk_image);
-        shared_resources.vulkan_memory.push(vk_memory);
-        shared_resources.memory_fds.push(memory_fd);
-    }
-    
-    // Create exportable semaphores
-    for _ in 0..buffer_count {
-        let (render_finished, consumer_ready) = unsafe { create_exportable_semaphores(
-            &raw_device,
-            &ext_semaphore_fd,
-        ) }?;
+            size: bevy::math::UVec2::new(config.width, config.height),
+            format,
+        });
         
-        shared_resources.render_finished_semaphores.push(render_finished);
-        shared_resources.consumer_ready_semaphores.push(consumer_ready);
+        if config.share_depth {
+            let (depth_image, depth_memory, depth_memory_fd) = unsafe { create_exportable_image_with_memory(
+                raw_device,
+                &ext_memory_fd,
+                &mem_properties,
+                config.width,
+                config.height,
+                SHARED_DEPTH_FORMAT,
+                None,
+            ) }?;
+            
+            buffer_set.images.depth.push(SharedBuffer {
+                device: raw_device.clone(),
+                image: depth_image,
+                memory: depth_memory,
+                memory_fd: depth_memory_fd,
+            });
+            
+            let depth_texture = unsafe { wrap_shared_image(
+                render_device,
+                depth_image,
+                config.width,
+                config.height,
+                CORE_3D_DEPTH_FORMAT,
+                &format!("shared_depth_texture_{}", i),
+            ) };
+            let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some(&format!("shared_depth_view_{}", i)),
+                ..Default::default()
+            });
+            
+            buffer_set.depth_textures.push(CachedTexture {
+                texture: depth_texture.into(),
+                default_view: depth_view.into(),
+            });
+        }
     }
     
-    info!("Successfully created {} shared textures and semaphores", buffer_count);
+    Ok(buffer_set)
+}
+
+// Checks that the config's format can be rendered to and sampled through wgpu, and
+// exported at the config's size, and returns its wgpu equivalent. DMA-BUF exports
+// are checked per modifier by `supported_drm_modifiers` instead.
+unsafe fn validate_shared_format(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    render_device: &RenderDevice,
+    config: &VulkanSharingConfig,
+) -> Result<wgpu::TextureFormat> {
+    let reject = |reason: &str| {
+        warn!("Cannot share textures in {:?}: {}", config.format, reason);
+        Err(ExternalSurfaceError::InvalidTextureFormat)
+    };
+    
+    let Some(format) = vk_format_to_wgpu(config.format) else {
+        return reject("no wgpu equivalent");
+    };
+    if format.is_depth_stencil_format() {
+        return reject("not a color format");
+    }
+    if !render_device.features().contains(format.required_features()) {
+        return reject(&format!("requires {:?}", format.required_features()));
+    }
+    
+    // sRGB formats encode on write, HDR color spaces need the range or precision
+    let float = matches!(format, wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float);
+    let color_space_supported = match config.color_space {
+        ColorSpace::Srgb => true,
+        ColorSpace::Linear => !format.is_srgb(),
+        ColorSpace::Pq => float || matches!(
+            format,
+            wgpu::TextureFormat::Rgb10a2Unorm | wgpu::TextureFormat::Rgba16Unorm,
+        ),
+        ColorSpace::ScRgb => float,
+    };
+    if !color_space_supported {
+        return reject(&format!("cannot hold {:?} values", config.color_space));
+    }
+    
+    if config.memory_export != MemoryExportMode::OpaqueFd {
+        return Ok(format);
+    }
+    
+    if let Err(reason) = unsafe {
+        check_opaque_fd_export(instance, physical_device, config.format, config.width, config.height)
+    } {
+        return reject(&reason);
+    }
+    
+    Ok(format)
+}
+
+// Whether images of `format` can be shared as opaque fds at the given size, and
+// if not, why
+unsafe fn check_opaque_fd_export(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+    width: u32,
+    height: u32,
+) -> std::result::Result<(), String> {
+    let mut external_info = vk::PhysicalDeviceExternalImageFormatInfo::default()
+        .handle_type(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+    let format_info = vk::PhysicalDeviceImageFormatInfo2::default()
+        .format(format)
+        .ty(vk::ImageType::TYPE_2D)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(shared_image_usage(format))
+        .push_next(&mut external_info);
+    
+    let mut external_properties = vk::ExternalImageFormatProperties::default();
+    let mut image_properties = vk::ImageFormatProperties2::default().push_next(&mut external_properties);
+    
+    let supported = unsafe {
+        instance.get_physical_device_image_format_properties2(physical_device, &format_info, &mut image_properties)
+    };
+    if let Err(e) = supported {
+        return Err(format!("not supported for shared images ({:?})", e));
+    }
+    
+    let max_extent = image_properties.image_format_properties.max_extent;
+    if max_extent.width < width || max_extent.height < height {
+        return Err(format!("at most {}x{} supported", max_extent.width, max_extent.height));
+    }
+    
+    let exportable = external_properties.external_memory_properties.external_memory_features
+        .contains(vk::ExternalMemoryFeatureFlags::EXPORTABLE);
+    if !exportable {
+        return Err("cannot be exported as an opaque fd".into());
+    }
     
     Ok(())
 }
 
+// Exports a DMA-BUF with one of `drm_modifiers` when given, an opaque fd otherwise
 unsafe fn create_exportable_image_with_memory(
     device: &ash::Device,
     ext_memory_fd: &ash::khr::external_memory_fd::Device,
@@ -360,27 +1112,42 @@ unsafe fn create_exportable_image_with_memory(
     width: u32,
     height: u32,
     format: vk::Format,
+    drm_modifiers: Option<&[u64]>,
 ) -> Result<(vk::Image, vk::DeviceMemory, RawFd)> {
+    let (handle_type, tiling) = match drm_modifiers {
+        Some(_) => (vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT, vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT),
+        None => (vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD, vk::ImageTiling::OPTIMAL),
+    };
+    
     // External memory image create info
     let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
-        .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+        .handle_types(handle_type);
+    let mut modifier_list_info = vk::ImageDrmFormatModifierListCreateInfoEXT::default()
+        .drm_format_modifiers(drm_modifiers.unwrap_or_default());
     
     // Image create info
-    let image_info = vk::ImageCreateInfo::default()
+    let mut image_info = vk::ImageCreateInfo::default()
         .image_type(vk::ImageType::TYPE_2D)
         .format(format)
         .extent(vk::Extent3D { width, height, depth: 1 })
         .mip_levels(1)
         .array_layers(1)
         .samples(vk::SampleCountFlags::TYPE_1)
-        .tiling(vk::ImageTiling::OPTIMAL)
-        .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+        .tiling(tiling)
+        .usage(shared_image_usage(format))
         .sharing_mode(vk::SharingMode::EXCLUSIVE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
         .push_next(&mut external_memory_info);
+    if drm_modifiers.is_some() {
+        image_info = image_info.push_next(&mut modifier_list_info);
+    }
     
     let vk_image = unsafe { device.create_image(&image_info, None) }
         .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to create image: {:?}", e)))?;
+    let fail = |e: ExternalSurfaceError| {
+        unsafe { device.destroy_image(vk_image, None) };
+        e
+    };
     
     // Get memory requirements
     let mem_reqs = unsafe { device.get_image_memory_requirements(vk_image) };
@@ -390,55 +1157,239 @@ unsafe fn create_exportable_image_with_memory(
         mem_properties,
         mem_reqs.memory_type_bits,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    )?;
+    )
+    .map_err(fail)?;
     
-    // Export memory allocate info
+    // Export memory allocate info. Dedicated allocations are what drivers expect
+    // for exported images, and importers have to match it.
     let mut export_info = vk::ExportMemoryAllocateInfo::default()
-        .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+        .handle_types(handle_type);
+    let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(vk_image);
     
     let alloc_info = vk::MemoryAllocateInfo::default()
         .allocation_size(mem_reqs.size)
         .memory_type_index(memory_type_index)
-        .push_next(&mut export_info);
+        .push_next(&mut export_info)
+        .push_next(&mut dedicated_info);
     
     let vk_memory = unsafe { device.allocate_memory(&alloc_info, None) }
-        .map_err(|e| ExternalSurfaceError::MemoryExportFailed(format!("Failed to allocate memory: {:?}", e)))?;
+        .map_err(|e| fail(ExternalSurfaceError::MemoryExportFailed(format!("Failed to allocate memory: {:?}", e))))?;
+    let fail = |e: ExternalSurfaceError| {
+        unsafe {
+            device.destroy_image(vk_image, None);
+            device.free_memory(vk_memory, None);
+        }
+        e
+    };
     
     // Bind memory to image
     unsafe { device.bind_image_memory(vk_image, vk_memory, 0) }
-        .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to bind memory: {:?}", e)))?;
+        .map_err(|e| fail(ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to bind memory: {:?}", e))))?;
     
     // Export memory fd
     let fd_info = vk::MemoryGetFdInfoKHR::default()
         .memory(vk_memory)
-        .handle_type(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+        .handle_type(handle_type);
     
     let fd = unsafe { ext_memory_fd.get_memory_fd(&fd_info) }
-        .map_err(|e| ExternalSurfaceError::MemoryExportFailed(format!("Failed to export fd: {:?}", e)))?;
+        .map_err(|e| fail(ExternalSurfaceError::MemoryExportFailed(format!("Failed to export fd: {:?}", e))))?;
     
     Ok((vk_image, vk_memory, fd))
 }
 
-unsafe fn create_exportable_semaphores(
+// DRM format modifiers the shared images can be rendered to, sampled from and
+// exported as DMA-BUFs with, in the driver's order of preference
+unsafe fn supported_drm_modifiers(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    config: &VulkanSharingConfig,
+    allowed_modifiers: &[u64],
+) -> Result<Vec<vk::DrmFormatModifierPropertiesEXT>> {
+    // Two calls, the first one only fills in the count
+    let mut modifier_count = vk::DrmFormatModifierPropertiesListEXT::default();
+    let mut format_properties = vk::FormatProperties2::default().push_next(&mut modifier_count);
+    unsafe { instance.get_physical_device_format_properties2(physical_device, config.format, &mut format_properties) };
+    
+    let mut modifiers = vec![vk::DrmFormatModifierPropertiesEXT::default(); modifier_count.drm_format_modifier_count as usize];
+    let mut modifier_list = vk::DrmFormatModifierPropertiesListEXT::default().drm_format_modifier_properties(&mut modifiers);
+    let mut format_properties = vk::FormatProperties2::default().push_next(&mut modifier_list);
+    unsafe { instance.get_physical_device_format_properties2(physical_device, config.format, &mut format_properties) };
+    let modifier_count = modifier_list.drm_format_modifier_count as usize;
+    modifiers.truncate(modifier_count);
+    
+    let required_features = vk::FormatFeatureFlags::COLOR_ATTACHMENT
+        | vk::FormatFeatureFlags::SAMPLED_IMAGE
+        | vk::FormatFeatureFlags::TRANSFER_SRC;
+    
+    modifiers.retain(|properties| {
+        if !properties.drm_format_modifier_tiling_features.contains(required_features) {
+            return false;
+        }
+        if !allowed_modifiers.is_empty() && !allowed_modifiers.contains(&properties.drm_format_modifier) {
+            return false;
+        }
+        
+        // The format features don't cover the usage, size and exportability
+        let mut modifier_info = vk::PhysicalDeviceImageDrmFormatModifierInfoEXT::default()
+            .drm_format_modifier(properties.drm_format_modifier)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let mut external_info = vk::PhysicalDeviceExternalImageFormatInfo::default()
+            .handle_type(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+        let format_info = vk::PhysicalDeviceImageFormatInfo2::default()
+            .format(config.format)
+            .ty(vk::ImageType::TYPE_2D)
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .usage(shared_image_usage(config.format))
+            .push_next(&mut modifier_info)
+            .push_next(&mut external_info);
+        
+        let mut external_properties = vk::ExternalImageFormatProperties::default();
+        let mut image_properties = vk::ImageFormatProperties2::default().push_next(&mut external_properties);
+        
+        let supported = unsafe {
+            instance.get_physical_device_image_format_properties2(physical_device, &format_info, &mut image_properties)
+        };
+        if supported.is_err() {
+            return false;
+        }
+        
+        let max_extent = image_properties.image_format_properties.max_extent;
+        let exportable = external_properties.external_memory_properties.external_memory_features
+            .contains(vk::ExternalMemoryFeatureFlags::EXPORTABLE);
+        
+        exportable && max_extent.width >= config.width && max_extent.height >= config.height
+    });
+    
+    if modifiers.is_empty() {
+        return Err(ExternalSurfaceError::MemoryExportFailed(format!(
+            "No DRM format modifier of {:?} can be exported as a DMA-BUF",
+            config.format,
+        )));
+    }
+    
+    Ok(modifiers)
+}
+
+// Reads back the modifier the driver picked for `image` and its plane layout
+unsafe fn dma_buf_layout(
     device: &ash::Device,
-    ext_semaphore_fd: &ash::khr::external_semaphore_fd::Device,
-) -> Result<(vk::Semaphore, vk::Semaphore)> {
-    let mut export_info = vk::ExportSemaphoreCreateInfo::default()
-        .handle_types(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD);
+    ext_drm_modifier: &ash::ext::image_drm_format_modifier::Device,
+    image: vk::Image,
+    format: vk::Format,
+    modifiers: &[vk::DrmFormatModifierPropertiesEXT],
+) -> Result<DmaBufLayout> {
+    const PLANE_ASPECTS: [vk::ImageAspectFlags; 4] = [
+        vk::ImageAspectFlags::MEMORY_PLANE_0_EXT,
+        vk::ImageAspectFlags::MEMORY_PLANE_1_EXT,
+        vk::ImageAspectFlags::MEMORY_PLANE_2_EXT,
+        vk::ImageAspectFlags::MEMORY_PLANE_3_EXT,
+    ];
+    
+    let fourcc = drm_fourcc(format).ok_or(ExternalSurfaceError::InvalidTextureFormat)?;
+    
+    let mut modifier_properties = vk::ImageDrmFormatModifierPropertiesEXT::default();
+    unsafe { ext_drm_modifier.get_image_drm_format_modifier_properties(image, &mut modifier_properties) }
+        .map_err(|e| ExternalSurfaceError::MemoryExportFailed(format!("Failed to query DRM format modifier: {:?}", e)))?;
+    let modifier = modifier_properties.drm_format_modifier;
+    
+    let plane_count = modifiers.iter()
+        .find(|properties| properties.drm_format_modifier == modifier)
+        .map_or(1, |properties| properties.drm_format_modifier_plane_count as usize);
+    
+    let planes = PLANE_ASPECTS[..plane_count.min(PLANE_ASPECTS.len())].iter()
+        .map(|&aspect_mask| {
+            let subresource = vk::ImageSubresource {
+                aspect_mask,
+                mip_level: 0,
+                array_layer: 0,
+            };
+            let layout = unsafe { device.get_image_subresource_layout(image, subresource) };
+            
+            DmaBufPlane {
+                offset: layout.offset,
+                stride: layout.row_pitch,
+            }
+        })
+        .collect();
     
-    let create_info = vk::SemaphoreCreateInfo::default()
-        .push_next(&mut export_info);
+    Ok(DmaBufLayout {
+        fourcc,
+        modifier,
+        planes,
+    })
+}
+
+// DRM_FORMAT_* fourcc of the shared formats. DRM names formats by their packed
+// little-endian layout, so Vulkan's B8G8R8A8 is DRM's ARGB8888.
+fn drm_fourcc(format: vk::Format) -> Option<u32> {
+    let fourcc = |code: &[u8; 4]| u32::from_le_bytes(*code);
     
-    let render_finished = unsafe { device.create_semaphore(&create_info, None) }
-        .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to create semaphore: {:?}", e)))?;
+    match format {
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => Some(fourcc(b"AR24")),
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Some(fourcc(b"AB24")),
+        vk::Format::A2B10G10R10_UNORM_PACK32 => Some(fourcc(b"AB30")),
+        vk::Format::R16G16B16A16_SFLOAT => Some(fourcc(b"AB4H")),
+        _ => None,
+    }
+}
+
+// Wraps a shared VkImage into a wgpu texture. The image is not owned by the
+// texture and has to outlive it.
+pub(crate) unsafe fn wrap_shared_image(
+    render_device: &RenderDevice,
+    vk_image: vk::Image,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    label: &str,
+) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    
+    // Matching `shared_image_usage`
+    let (hal_usage, usage) = shared_texture_uses(format);
+    
+    let hal_desc = wgpu_hal::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: hal_usage,
+        memory_flags: wgpu_hal::MemoryFlags::empty(),
+        view_formats: vec![],
+    };
     
-    let consumer_ready = unsafe { device.create_semaphore(&create_info, None) }
-        .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to create semaphore: {:?}", e)))?;
+    let hal_texture = unsafe {
+        wgpu_hal::vulkan::Device::texture_from_raw(
+            vk_image,
+            &hal_desc,
+            Some(Box::new(|| {})), // No-op drop callback - we manage lifetime
+        )
+    };
     
-    Ok((render_finished, consumer_ready))
+    let wgpu_desc = wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    };
+    
+    unsafe {
+        render_device.wgpu_device()
+            .create_texture_from_hal::<VulkanApi>(hal_texture, &wgpu_desc)
+    }
 }
 
-fn find_memory_type(
+pub(crate) fn find_memory_type(
     mem_properties: &vk::PhysicalDeviceMemoryProperties,
     type_filter: u32,
     properties: vk::MemoryPropertyFlags,
@@ -453,60 +1404,291 @@ fn find_memory_type(
     Err(ExternalSurfaceError::MemoryExportFailed("No suitable memory type found".into()))
 }
 
-fn convert_vk_format_to_wgpu(format: vk::Format) -> wgpu::TextureFormat {
-    match format {
-        vk::Format::B8G8R8A8_SRGB => wgpu::TextureFormat::Bgra8UnormSrgb,
-        vk::Format::B8G8R8A8_UNORM => wgpu::TextureFormat::Bgra8Unorm,
-        vk::Format::R8G8B8A8_SRGB => wgpu::TextureFormat::Rgba8UnormSrgb,
-        vk::Format::R8G8B8A8_UNORM => wgpu::TextureFormat::Rgba8Unorm,
-        _ => wgpu::TextureFormat::Bgra8UnormSrgb,
+// Notices consumers hanging up, attaches waiting ones without ever blocking the
+// frame, and reports both as events
+fn poll_consumer_connection(
+    shared_surfaces: Res<SharedSurfaces>,
+    render_device: Res<RenderDevice>,
+    mut connected_events: EventWriter<ConsumerConnected>,
+    mut disconnected_events: EventWriter<ConsumerDisconnected>,
+    mut resize_events: EventWriter<ResizeSharedSurface>,
+) {
+    for shared_resources in &shared_surfaces.surfaces {
+        let surface = shared_resources.id;
+        let Some(handler) = &shared_resources.ipc_handler else {
+            continue;
+        };
+        let Ok(mut handler) = handler.lock() else {
+            continue;
+        };
+        
+        handler.receive_messages();
+        
+        if let Err(e) = handler.accept_clients(&render_device) {
+            warn!("Failed to accept consumer of {:?}: {}", surface, e);
+        }
+        
+        // Disconnects may also have been noticed by the render world while sending
+        for change in handler.take_connection_changes() {
+            match change {
+                ConnectionChange::Connected(consumer_id) => {
+                    connected_events.write(ConsumerConnected { surface, consumer_id });
+                }
+                ConnectionChange::Disconnected(consumer_id) => {
+                    disconnected_events.write(ConsumerDisconnected { surface, consumer_id });
+                }
+            }
+        }
+        
+        for request in handler.take_resize_requests() {
+            resize_events.write(ResizeSharedSurface {
+                surface,
+                width: request.width,
+                height: request.height,
+            });
+        }
+    }
+}
+
+// Swaps the depth texture Bevy prepared for cameras rendering into a shared
+// texture for the matching shared depth image. Those are single sampled, so
+// cameras using MSAA keep their own.
+fn use_shared_depth_textures(
+    shared_surfaces: Res<SharedSurfaces>,
+    mut views: Query<(&ExtractedCamera, &Camera3d, &Msaa, &mut ViewDepthTexture)>,
+    mut warned_msaa: Local<bool>,
+) {
+    for (camera, camera_3d, msaa, mut depth_texture) in views.iter_mut() {
+        let Some(NormalizedRenderTarget::TextureView(handle)) = camera.target else {
+            continue;
+        };
+        let Some(shared_depth_texture) = shared_surfaces.surfaces.iter().find_map(|surface| {
+            let buffer_index = surface.texture_handles.iter().position(|&shared| shared == handle)?;
+            surface.depth_textures.get(buffer_index)
+        }) else {
+            continue;
+        };
+        
+        if msaa.samples() > 1 {
+            if !*warned_msaa {
+                warn!("Not sharing depth of a camera using {}x MSAA, use Msaa::Off", msaa.samples());
+                *warned_msaa = true;
+            }
+            continue;
+        }
+        
+        let clear_value = match camera_3d.depth_load_op {
+            Camera3dDepthLoadOp::Clear(value) => Some(value),
+            Camera3dDepthLoadOp::Load => None,
+        };
+        *depth_texture = ViewDepthTexture::new(shared_depth_texture.clone(), clear_value);
     }
 }
 
-fn wait_for_consumer(_shared_resources: Res<SharedVulkanResources>) {
-    // TODO: Implement actual semaphore waiting
-    // This would involve submitting a wait operation to the GPU queue
+// Makes this frame's rendering wait until consumers are done with the buffer it
+// renders into. Their releases are waited for on the host without holding the
+// IPC lock, then their signals on the GPU.
+fn wait_for_consumers(world: &mut World) {
+    let shared_surfaces = world.resource::<SharedSurfaces>();
+    let render_queue = world.resource::<RenderQueue>();
+    
+    for shared_resources in &shared_surfaces.surfaces {
+        let Some(handler) = &shared_resources.ipc_handler else {
+            continue;
+        };
+        let current_idx = shared_resources.current_buffer_index;
+        
+        // Only timeline mode may overwrite a buffer consumers hold
+        let config = &shared_resources.config;
+        if (config.swap_policy, config.sync_mode) != (BufferSwapPolicy::Immediate, FrameSyncMode::Timeline) {
+            wait_for_release(handler, current_idx, CONSUMER_WAIT_TIMEOUT);
+        }
+        
+        let Ok(mut handler) = handler.lock() else {
+            continue;
+        };
+        // Exclusive system, nothing else uses the queue
+        if let Err(e) = unsafe { handler.wait_for_released_frames(current_idx, render_queue) } {
+            error!("Failed to wait for consumers of {:?} on buffer {}: {}", shared_resources.id, current_idx, e);
+        }
+    }
+}
+
+// Signals this frame's render_finished after everything rendered into the buffer
+// and announces the frame
+fn signal_render_finished(world: &mut World) {
+    let shared_surfaces = world.resource::<SharedSurfaces>();
+    let render_queue = world.resource::<RenderQueue>();
+    
+    for shared_resources in &shared_surfaces.surfaces {
+        // Exclusive system, nothing else uses the queue
+        if let Err(e) = unsafe { signal_surface_render_finished(shared_resources, render_queue) } {
+            error!("Failed to signal frame of {:?}: {}", shared_resources.id, e);
+        }
+    }
+}
+
+unsafe fn signal_surface_render_finished(
+    shared_resources: &SharedVulkanResources,
+    render_queue: &RenderQueue,
+) -> Result<()> {
+    let (Some(handler), Some(sync)) = (&shared_resources.ipc_handler, &shared_resources.sync) else {
+        return Ok(());
+    };
+    let Ok(mut handler) = handler.lock() else {
+        return Ok(());
+    };
+    if !handler.has_clients() {
+        return Ok(());
+    }
+    
+    let current_idx = shared_resources.current_buffer_index;
+    
+    if let Some(timeline) = &sync.timeline {
+        let frame_value = unsafe { timeline.signal_frame(render_queue) }?;
+        handler.send_frame_ready(current_idx, frame_value, &[]);
+    } else if let Some(render_finished) = &sync.render_finished {
+        // Exporting moves the pending signal into the sync fd, leaving the semaphore
+        // unsignaled for the next frame
+        unsafe { render_finished.queue_signal(render_queue, 0) }?;
+        let render_finished_fd = render_finished.export_fd().inspect_err(|_| {
+            // Consumes the signal instead, the next one requires it unsignaled
+            let _ = unsafe { render_finished.queue_wait(render_queue, 0) };
+        })?;
+        
+        handler.send_frame_ready(current_idx, 0, &[render_finished_fd]);
+        
+        // Every consumer received its own copy
+        unsafe { libc::close(render_finished_fd) };
+    }
+    
+    Ok(())
 }
 
-fn signal_render_finished(
-    mut shared_resources: ResMut<SharedVulkanResources>,
+// Frees the images replaced by a resize once the frames submitted before they were
+// retired, the last ones that could have rendered into them, have completed. They
+// are dropped here rather than in wgpu's callback, which must not call back into it.
+fn retire_shared_buffers(
+    shared_surfaces: Res<SharedSurfaces>,
+    render_queue: Res<RenderQueue>,
 ) {
-    // Re-export semaphore FDs for next frame (they're consumed on import)
-    if let (Some(device), Some(handler)) = (&shared_resources.device, &shared_resources.ipc_handler) {
-        if let Ok(mut handler) = handler.lock() {
-            // Export fresh semaphore FDs for this frame
-            let current_idx = shared_resources.current_buffer_index;
+    for shared_resources in &shared_surfaces.surfaces {
+        let Ok(mut retired_images) = shared_resources.retired_images.lock() else {
+            continue;
+        };
+        
+        retired_images.retain(|retired| !retired.images.idle.load(Ordering::Acquire));
+        
+        for retired in retired_images.iter_mut().filter(|retired| !retired.watched) {
+            let images = retired.images.clone();
+            render_queue.on_submitted_work_done(move || images.idle.store(true, Ordering::Release));
+            retired.watched = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ConnectionChange {
+    Connected(u64),
+    Disconnected(u64),
+}
+
+// An accepted socket and the messages arriving on it
+#[cfg(unix)]
+struct Connection {
+    fd: RawFd,
+    reader: MessageReader,
+}
+
+#[cfg(unix)]
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+// Semaphores a consumer signals once it is done with a buffer
+#[cfg(unix)]
+enum ConsumerReadySync {
+    // One binary semaphore per buffer, exported once with the metadata and
+    // signalled once per frame sent from the buffer
+    Binary(Vec<ExternalSemaphore>),
+    // One timeline semaphore, exported once with the metadata, and the value of the
+    // last frame sent to the consumer from each buffer (0 if none)
+    Timeline {
+        semaphore: Box<ExternalSemaphore>,
+        buffer_frame_values: Vec<u64>,
+    },
+}
+
+#[cfg(unix)]
+struct ConsumerConnection {
+    id: u64,
+    name: String,
+    connection: Connection,
+    consumer_ready: ConsumerReadySync,
+    // Frames sent from each buffer that the consumer has not released yet
+    outstanding_frames: Vec<u32>,
+    // Frames released from each buffer whose signal the GPU has not waited on yet
+    released_frames: Vec<u32>,
+    // Latest size asked for and not yet reported
+    resize_request: Option<ResizeRequest>,
+}
+
+#[cfg(unix)]
+impl ConsumerConnection {
+    // Handles every message that has arrived. Returns false once the consumer has
+    // hung up.
+    fn receive_messages(&mut self) -> Result<bool> {
+        loop {
+            let mut message = match self.connection.reader.try_read_message(self.connection.fd) {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(true),
+                Err(ExternalSurfaceError::IpcDisconnected) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            message.close_fds();
             
-            // TODO: Export fresh FDs for semaphores and send via IPC
+            if message.kind == MessageKind::ResizeRequest {
+                self.resize_request = Some(message.decode::<ResizeRequest>()?);
+                continue;
+            }
             
-            let frame_info = IPCFrameInfo {
-                buffer_index: current_idx,
-                render_finished_semaphore_fd: None, // Would be freshly exported
-                consumer_ready_semaphore_fd: None,  // Would be freshly exported
-            };
+            let released = message.decode::<BufferReleased>()?;
+            let index = released.buffer_index as usize;
             
-            if let Err(e) = handler.send_frame_ready(&frame_info) {
-                warn!("Failed to send frame info: {}", e);
+            // An index that was never sent would have no signal to pair a wait with
+            if let Some(outstanding) = self.outstanding_frames.get_mut(index)
+                && *outstanding > 0
+            {
+                *outstanding -= 1;
+                self.released_frames[index] += 1;
             }
         }
     }
-    
-    shared_resources.swap_buffers();
 }
 
 // IPC Handler implementation
 #[cfg(unix)]
 pub struct IPCHandler {
     socket_fd: RawFd,
-    client_fd: Option<RawFd>,
+    // Sent to each consumer with its id filled in, along with the shared fds: the
+    // memory fds and, in timeline mode, the render_finished timeline
+    metadata: Metadata,
+    shared_fds: Vec<RawFd>,
+    // Accepted connections that have not sent their `Hello` yet
+    pending: Vec<Connection>,
+    clients: Vec<ConsumerConnection>,
+    next_client_id: u64,
+    // Connection changes not yet reported as events
+    connection_changes: Vec<ConnectionChange>,
 }
 
 #[cfg(unix)]
 impl IPCHandler {
-    fn new_server(socket_path: &str) -> Result<Self> {
-        use std::os::unix::fs::DirBuilderExt;
-        use std::path::Path;
-        
+    fn new_server(socket_path: &str, metadata: Metadata, shared_fds: Vec<RawFd>) -> Result<Self> {
         // Remove existing socket file
         let _ = std::fs::remove_file(socket_path);
         
@@ -525,94 +1707,374 @@ impl IPCHandler {
         socket::bind(socket_fd.as_raw_fd(), &addr)
             .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to bind socket: {}", e)))?;
         
-        // Listen for connections
-        socket::listen(&socket_fd, socket::Backlog::new(1).unwrap())
+        // Listen for connections. Accepting is polled every frame, so it must not block.
+        socket::listen(&socket_fd, socket::Backlog::new(MAX_PENDING_CONSUMERS).unwrap())
             .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to listen: {}", e)))?;
         
+        fcntl(socket_fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+            .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to make socket non-blocking: {}", e)))?;
+        
         Ok(Self {
             socket_fd: socket_fd.into_raw_fd(),
-            client_fd: None,
+            metadata,
+            shared_fds,
+            pending: Vec::new(),
+            clients: Vec::new(),
+            next_client_id: 0,
+            connection_changes: Vec::new(),
         })
     }
     
-    fn send_initial_metadata(&mut self, metadata: &IPCMetadata) -> Result<()> {
-        // Accept client connection if not already connected
-        if self.client_fd.is_none() {
+    fn has_clients(&self) -> bool {
+        !self.clients.is_empty()
+    }
+    
+    // Accepts every pending connection and completes the handshake of those whose
+    // `Hello` has arrived
+    fn accept_clients(&mut self, render_device: &RenderDevice) -> Result<()> {
+        let accepted = loop {
             match socket::accept(self.socket_fd) {
-                Ok(client_fd) => {
-                    self.client_fd = Some(client_fd);
-                    info!("Client connected to IPC socket");
+                Ok(fd) => {
+                    let connection = Connection { fd, reader: MessageReader::new() };
+                    
+                    // Sends happen on the render thread, a consumer that stops reading
+                    // must not block it. A send that would block drops the consumer.
+                    if let Err(e) = fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+                        warn!("Failed to make consumer socket non-blocking: {}", e);
+                        continue;
+                    }
+                    self.pending.push(connection);
                 }
-                Err(_) => {
-                    // No client connected yet
-                    return Ok(());
+                Err(nix::errno::Errno::EAGAIN) => break Ok(()),
+                Err(e) => {
+                    break Err(ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to accept client: {}", e)));
                 }
             }
-        }
+        };
         
-        if let Some(client_fd) = self.client_fd {
-            // Serialize metadata
-            let data = bincode::serialize(metadata)
-                .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to serialize: {}", e)))?;
-            
-            // Send file descriptors as ancillary data
-            let fds: Vec<RawFd> = metadata.memory_fds.clone();
-            let cmsg = socket::ControlMessage::ScmRights(&fds);
+        for mut connection in std::mem::take(&mut self.pending) {
+            let hello = match connection.reader.try_read_message(connection.fd) {
+                Ok(Some(mut message)) => {
+                    message.close_fds();
+                    message.decode::<Hello>()
+                }
+                Ok(None) => {
+                    self.pending.push(connection);
+                    continue;
+                }
+                Err(e) => Err(e),
+            };
             
-            socket::sendmsg::<()>(
-                client_fd,
-                &[std::io::IoSlice::new(&data)],
-                &[cmsg],
-                MsgFlags::empty(),
-                None,
-            ).map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to send metadata: {}", e)))?;
+            let result = hello.and_then(|hello| self.connect_client(connection, hello, render_device));
+            match result {
+                Ok(()) | Err(ExternalSurfaceError::IpcDisconnected) => {}
+                Err(e) => warn!("Consumer handshake failed: {}", e),
+            }
+        }
+        
+        accepted
+    }
+    
+    fn connect_client(&mut self, connection: Connection, hello: Hello, render_device: &RenderDevice) -> Result<()> {
+        let buffer_count = self.metadata.buffer_count as usize;
+        
+        let required = match self.metadata.sync_mode {
+            FrameSyncMode::Binary => Capabilities::SYNC_FD,
+            FrameSyncMode::Timeline => Capabilities::TIMELINE_SEMAPHORES,
+        };
+        let reason = if !hello.capabilities.contains(required) {
+            Some(format!("{:?} sync mode is not supported by the consumer", self.metadata.sync_mode))
+        } else if self.metadata.memory_handle_type == MemoryHandleType::DmaBuf
+            && !hello.capabilities.contains(Capabilities::DMA_BUF)
+        {
+            Some("DMA-BUF memory is not supported by the consumer".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            let _ = send_message(connection.fd, &Reject { reason: reason.clone() }, &[]);
+            return Err(ExternalSurfaceError::IpcProtocolError(format!("Rejected {}: {}", hello.name, reason)));
+        }
+        
+        let mut fds = self.shared_fds.clone();
+        let consumer_ready = match self.metadata.sync_mode {
+            FrameSyncMode::Binary => {
+                let mut semaphores = Vec::new();
+                for _ in 0..buffer_count {
+                    let semaphore = ExternalSemaphore::create_exportable(
+                        render_device,
+                        SemaphoreKind::Binary,
+                        SemaphoreHandleType::OpaqueFd,
+                    )?;
+                    fds.push(semaphore.export_fd()?);
+                    semaphores.push(semaphore);
+                }
+                
+                ConsumerReadySync::Binary(semaphores)
+            }
+            FrameSyncMode::Timeline => {
+                let semaphore = ExternalSemaphore::create_exportable(
+                    render_device,
+                    SemaphoreKind::Timeline,
+                    SemaphoreHandleType::OpaqueFd,
+                )?;
+                fds.push(semaphore.export_fd()?);
+                
+                ConsumerReadySync::Timeline {
+                    semaphore: Box::new(semaphore),
+                    buffer_frame_values: vec![0; buffer_count],
+                }
+            }
+        };
+        
+        let id = self.next_client_id;
+        let metadata = Metadata { consumer_id: id, ..self.metadata.clone() };
+        
+        // The memory fds are duplicated into every client by SCM_RIGHTS, so a
+        // reconnecting consumer gets fresh ones
+        let sent = send_message(connection.fd, &metadata, &fds);
+        for &fd in &fds[self.shared_fds.len()..] {
+            unsafe { libc::close(fd) };
         }
+        sent?;
         
+        self.next_client_id += 1;
+        info!("Consumer {} ({}) connected to IPC socket", id, hello.name);
+        
+        self.clients.push(ConsumerConnection {
+            id,
+            name: hello.name,
+            connection,
+            consumer_ready,
+            outstanding_frames: vec![0; buffer_count],
+            released_frames: vec![0; buffer_count],
+            resize_request: None,
+        });
+        
+        self.connection_changes.push(ConnectionChange::Connected(id));
         Ok(())
     }
     
-    fn send_frame_ready(&mut self, frame_info: &IPCFrameInfo) -> Result<()> {
-        if let Some(client_fd) = self.client_fd {
-            // Serialize frame info
-            let data = bincode::serialize(frame_info)
-                .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to serialize: {}", e)))?;
+    // Drops a client, its semaphores go with it
+    fn disconnect_client(&mut self, index: usize) {
+        let client = self.clients.remove(index);
+        
+        info!("Consumer {} ({}) disconnected from IPC socket", client.id, client.name);
+        self.connection_changes.push(ConnectionChange::Disconnected(client.id));
+    }
+    
+    fn take_connection_changes(&mut self) -> Vec<ConnectionChange> {
+        std::mem::take(&mut self.connection_changes)
+    }
+    
+    fn take_resize_requests(&mut self) -> Vec<ResizeRequest> {
+        self.clients.iter_mut().filter_map(|client| client.resize_request.take()).collect()
+    }
+    
+    // Only reaches consumers connecting or resizing afterwards
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.metadata.camera_hdr = state.hdr;
+        self.metadata.tonemapping = state.tonemapping;
+        self.metadata.projection = state.projection;
+    }
+    
+    // Switches to the images of a resize and sends every consumer the new metadata.
+    // Synchronization is per buffer index and carries over, so frames sent before
+    // are released as usual.
+    fn resize(&mut self, width: u32, height: u32, dma_buf_layouts: Vec<DmaBufLayout>, memory_fds: &[RawFd]) {
+        self.metadata.width = width;
+        self.metadata.height = height;
+        self.metadata.dma_buf_layouts = dma_buf_layouts;
+        self.shared_fds.splice(..memory_fds.len(), memory_fds.iter().copied());
+        
+        let mut disconnected = Vec::new();
+        
+        for (client_index, client) in self.clients.iter().enumerate() {
+            let metadata = Metadata { consumer_id: client.id, ..self.metadata.clone() };
             
-            // Collect FDs to send (semaphores)
-            let mut fds = Vec::new();
-            if let Some(fd) = frame_info.render_finished_semaphore_fd {
-                fds.push(fd);
+            // A consumer that missed the resize can't make sense of later frames
+            match send_message(client.connection.fd, &metadata, memory_fds) {
+                Ok(()) => {}
+                Err(ExternalSurfaceError::IpcDisconnected) => disconnected.push(client_index),
+                Err(e) => {
+                    warn!("Dropping consumer {}: {}", client.id, e);
+                    disconnected.push(client_index);
+                }
+            }
+        }
+        
+        for client_index in disconnected.into_iter().rev() {
+            self.disconnect_client(client_index);
+        }
+    }
+    
+    // Announces a frame to every consumer, with the fds of the frame's sync
+    fn send_frame_ready(&mut self, buffer_index: usize, frame_value: u64, fds: &[RawFd]) {
+        let frame = FrameReady {
+            buffer_index: buffer_index as u32,
+            frame_value,
+        };
+        let mut disconnected = Vec::new();
+        
+        for (client_index, client) in self.clients.iter_mut().enumerate() {
+            match send_message(client.connection.fd, &frame, fds) {
+                Ok(()) => {
+                    client.outstanding_frames[buffer_index] += 1;
+                    if let ConsumerReadySync::Timeline { buffer_frame_values, .. } = &mut client.consumer_ready {
+                        buffer_frame_values[buffer_index] = frame_value;
+                    }
+                }
+                Err(ExternalSurfaceError::IpcDisconnected) => disconnected.push(client_index),
+                // A consumer that missed a frame would never release it
+                Err(e) => {
+                    warn!("Dropping consumer {}: {}", client.id, e);
+                    disconnected.push(client_index);
+                }
+            }
+        }
+        
+        for client_index in disconnected.into_iter().rev() {
+            self.disconnect_client(client_index);
+        }
+    }
+    
+    // Handles buffer releases and resize requests from every consumer, dropping
+    // those that hung up or broke the protocol
+    fn receive_messages(&mut self) {
+        let mut disconnected = Vec::new();
+        
+        for (client_index, client) in self.clients.iter_mut().enumerate() {
+            match client.receive_messages() {
+                Ok(true) => {}
+                Ok(false) => disconnected.push(client_index),
+                Err(e) => {
+                    warn!("Dropping consumer {}: {}", client.id, e);
+                    disconnected.push(client_index);
+                }
             }
-            if let Some(fd) = frame_info.consumer_ready_semaphore_fd {
-                fds.push(fd);
+        }
+        
+        for client_index in disconnected.into_iter().rev() {
+            self.disconnect_client(client_index);
+        }
+    }
+    
+    // Whether no consumer still holds a frame from the buffer
+    fn is_buffer_free(&self, buffer_index: usize) -> bool {
+        self.clients.iter().all(|client| {
+            client.outstanding_frames.get(buffer_index).is_none_or(|&count| count == 0)
+        })
+    }
+    
+    // Indices and sockets of the consumers holding a frame from the buffer
+    fn clients_holding(&self, buffer_index: usize) -> Vec<(usize, RawFd)> {
+        self.clients.iter()
+            .enumerate()
+            .filter(|(_, client)| client.outstanding_frames.get(buffer_index).is_some_and(|&count| count > 0))
+            .map(|(client_index, client)| (client_index, client.connection.fd))
+            .collect()
+    }
+    
+    // Drops the consumers still holding the buffer once `wait_for_release` gave up
+    fn drop_stalled_clients(&mut self, buffer_index: usize, timeout: Duration) {
+        let stalled: Vec<usize> = self.clients_holding(buffer_index)
+            .into_iter()
+            .map(|(client_index, _)| client_index)
+            .collect();
+        
+        for client_index in stalled.into_iter().rev() {
+            warn!(
+                "Dropping consumer {}: buffer {} not released within {:?}",
+                self.clients[client_index].id,
+                buffer_index,
+                timeout,
+            );
+            self.disconnect_client(client_index);
+        }
+    }
+    
+    // Submits a wait for the consumer_ready signal of every frame released from the
+    // buffer, which the GPU has to pass before running anything submitted afterwards.
+    // Same requirements as `ExternalSemaphore::queue_wait`: consumers submit their
+    // signal before releasing, so every wait has one.
+    unsafe fn wait_for_released_frames(&mut self, buffer_index: usize, render_queue: &RenderQueue) -> Result<()> {
+        for client in &mut self.clients {
+            let Some(released) = client.released_frames.get_mut(buffer_index) else {
+                continue;
+            };
+            if *released == 0 {
+                continue;
             }
             
-            if !fds.is_empty() {
-                let cmsg = socket::ControlMessage::ScmRights(&fds);
-                socket::sendmsg::<()>(
-                    client_fd,
-                    &[std::io::IoSlice::new(&data)],
-                    &[cmsg],
-                    MsgFlags::empty(),
-                    None,
-                ).map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to send frame info: {}", e)))?;
-            } else {
-                // Send without FDs
-                socket::send(client_fd, &data, MsgFlags::empty())
-                    .map_err(|e| ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to send frame info: {}", e)))?;
+            match &client.consumer_ready {
+                // Binary semaphores take one wait per signal
+                ConsumerReadySync::Binary(semaphores) => {
+                    for _ in 0..*released {
+                        unsafe { semaphores[buffer_index].queue_wait(render_queue, 0) }?;
+                    }
+                }
+                // The last frame sent from the buffer covers the earlier ones
+                ConsumerReadySync::Timeline { semaphore, buffer_frame_values } => {
+                    unsafe { semaphore.queue_wait(render_queue, buffer_frame_values[buffer_index]) }?;
+                }
             }
+            *released = 0;
         }
         
         Ok(())
     }
 }
 
+// Waits until every consumer that was sent the buffer has released it. Consumers
+// that do not release it within `timeout` are dropped, so the buffer is never
+// rendered into while one of them may still be reading it. The lock is only held
+// while handling messages, the main world keeps picking buffers meanwhile.
+#[cfg(unix)]
+fn wait_for_release(handler: &Mutex<IPCHandler>, buffer_index: usize, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    
+    loop {
+        let Ok(mut locked) = handler.lock() else {
+            return;
+        };
+        locked.receive_messages();
+        
+        let holding = locked.clients_holding(buffer_index);
+        if holding.is_empty() {
+            return;
+        }
+        
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            locked.drop_stalled_clients(buffer_index, timeout);
+            return;
+        }
+        drop(locked);
+        
+        // The main world may drop a consumer and close its socket meanwhile, so
+        // the sockets are only polled briefly before checking again
+        let fds: Vec<RawFd> = holding.into_iter().map(|(_, fd)| fd).collect();
+        poll_readable(&fds, remaining.min(RELEASE_POLL_INTERVAL));
+    }
+}
+
+// Blocks until one of the fds is readable or the timeout passes
+#[cfg(unix)]
+fn poll_readable(fds: &[RawFd], timeout: Duration) {
+    let mut poll_fds: Vec<libc::pollfd> = fds.iter()
+        .map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+        .collect();
+    let timeout_ms = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+    
+    unsafe {
+        libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, timeout_ms);
+    }
+}
+
 #[cfg(unix)]
 impl Drop for IPCHandler {
     fn drop(&mut self) {
         unsafe {
-            if let Some(client_fd) = self.client_fd {
-                libc::close(client_fd);
-            }
             libc::close(self.socket_fd);
         }
     }
@@ -624,39 +2086,221 @@ pub struct IPCHandler;
 
 #[cfg(not(unix))]
 impl IPCHandler {
-    fn new_server(_socket_path: &str) -> Result<Self> {
+    fn new_server(_socket_path: &str, _metadata: Metadata, _shared_fds: Vec<RawFd>) -> Result<Self> {
         Err(ExternalSurfaceError::UnsupportedBackend("IPC not implemented for Windows yet".into()))
     }
     
-    fn send_initial_metadata(&mut self, _metadata: &IPCMetadata) -> Result<()> {
+    fn accept_clients(&mut self, _render_device: &RenderDevice) -> Result<()> {
         Ok(())
     }
     
-    fn send_frame_ready(&mut self, _frame_info: &IPCFrameInfo) -> Result<()> {
+    fn take_connection_changes(&mut self) -> Vec<ConnectionChange> {
+        Vec::new()
+    }
+    
+    fn take_resize_requests(&mut self) -> Vec<protocol::ResizeRequest> {
+        Vec::new()
+    }
+    
+    fn set_camera_state(&mut self, _state: CameraState) {}
+    
+    fn resize(&mut self, _width: u32, _height: u32, _dma_buf_layouts: Vec<DmaBufLayout>, _memory_fds: &[RawFd]) {}
+    
+    fn send_frame_ready(&mut self, _buffer_index: usize, _frame_value: u64, _fds: &[RawFd]) {}
+    
+    fn has_clients(&self) -> bool {
+        false
+    }
+    
+    fn receive_messages(&mut self) {}
+    
+    fn is_buffer_free(&self, _buffer_index: usize) -> bool {
+        true
+    }
+    
+    unsafe fn wait_for_released_frames(&mut self, _buffer_index: usize, _render_queue: &RenderQueue) -> Result<()> {
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct IPCMetadata {
-    width: u32,
-    height: u32,
-    format: u32,
-    #[cfg(unix)]
-    memory_fds: Vec<RawFd>,
-    #[cfg(not(unix))]
-    memory_handles: Vec<isize>,
-}
+#[cfg(not(unix))]
+fn wait_for_release(_handler: &Mutex<IPCHandler>, _buffer_index: usize, _timeout: Duration) {}
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct IPCFrameInfo {
-    buffer_index: usize,
+#[cfg(test)]
+mod tests {
+    use super::*;
     #[cfg(unix)]
-    render_finished_semaphore_fd: Option<RawFd>,
+    use std::os::unix::net::UnixStream;
+    
+    // A surface with `buffer_count` texture handles and no images, enough for
+    // picking buffers
+    fn surface(swap_policy: BufferSwapPolicy, buffer_count: u32) -> SharedVulkanResources {
+        let config = VulkanSharingConfig {
+            buffer_count,
+            swap_policy,
+            ipc_socket_path: None,
+            ..default()
+        };
+        
+        let mut surface = SharedVulkanResources::new(SharedSurfaceId::default(), config);
+        surface.texture_handles = (0..buffer_count).map(|index| ManualTextureViewHandle(100 + index)).collect();
+        surface
+    }
+    
+    // Runs `frames` frames of buffer picking and returns the buffer each camera
+    // rendered into, checking that it is the one announced
+    fn rendered_buffers(surface: SharedVulkanResources, frames: usize) -> Vec<usize> {
+        let mut world = World::new();
+        world.insert_resource(SharedSurfaces { surfaces: vec![surface] });
+        let camera = world.spawn((Camera::default(), SharedSurfaceTarget::default())).id();
+        
+        let mut schedule = Schedule::default();
+        schedule.add_systems((advance_shared_buffers, update_camera_targets).chain());
+        
+        (0..frames)
+            .map(|_| {
+                schedule.run(&mut world);
+                
+                let surface = world.resource::<SharedSurfaces>().get(SharedSurfaceId::default()).unwrap();
+                let handle = surface.get_current_texture_handle().unwrap();
+                let target = &world.get::<Camera>(camera).unwrap().target;
+                assert!(matches!(target, RenderTarget::TextureView(rendered) if *rendered == handle));
+                
+                surface.current_buffer_index
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn cameras_cycle_through_the_buffers() {
+        for swap_policy in [BufferSwapPolicy::Fifo, BufferSwapPolicy::Mailbox, BufferSwapPolicy::Immediate] {
+            assert_eq!(rendered_buffers(surface(swap_policy, 3), 7), [1, 2, 0, 1, 2, 0, 1]);
+        }
+    }
+    
+    #[test]
+    fn single_buffer_is_always_rendered_into() {
+        assert_eq!(rendered_buffers(surface(BufferSwapPolicy::Fifo, 1), 3), [0, 0, 0]);
+    }
+    
+    #[test]
+    fn cameras_of_other_surfaces_are_left_alone() {
+        let mut world = World::new();
+        world.insert_resource(SharedSurfaces { surfaces: vec![surface(BufferSwapPolicy::Fifo, 2)] });
+        let camera = world.spawn((Camera::default(), SharedSurfaceTarget(SharedSurfaceId(1)))).id();
+        
+        let mut schedule = Schedule::default();
+        schedule.add_systems((advance_shared_buffers, update_camera_targets).chain());
+        schedule.run(&mut world);
+        
+        let target = &world.get::<Camera>(camera).unwrap().target;
+        assert!(matches!(target, RenderTarget::Window(_)));
+    }
+    
+    // A binary mode producer of `buffer_count` buffers with one consumer attached,
+    // and the consumer's end of its socket. The consumer has no consumer_ready
+    // semaphores, so released frames are never waited on.
+    #[cfg(unix)]
+    fn binary_handler(name: &str, buffer_count: u32) -> (IPCHandler, UnixStream) {
+        let metadata = Metadata {
+            width: 64,
+            height: 64,
+            format: vk::Format::R8G8B8A8_UNORM.as_raw() as u32,
+            buffer_count,
+            sync_mode: FrameSyncMode::Binary,
+            memory_handle_type: MemoryHandleType::OpaqueFd,
+            dma_buf_layouts: Vec::new(),
+            color_space: ColorSpace::Srgb,
+            camera_hdr: false,
+            tonemapping: TonemappingOperator::None,
+            projection: ProjectionInfo::default(),
+            depth_format: None,
+            consumer_id: 0,
+        };
+        let socket_path = std::env::temp_dir().join(format!("vulkan_sharing_{}_{}.sock", name, std::process::id()));
+        let mut handler = IPCHandler::new_server(socket_path.to_str().unwrap(), metadata, Vec::new()).unwrap();
+        let _ = std::fs::remove_file(&socket_path);
+        
+        let (producer, consumer) = UnixStream::pair().unwrap();
+        producer.set_nonblocking(true).unwrap();
+        handler.clients.push(ConsumerConnection {
+            id: 0,
+            name: name.to_string(),
+            connection: Connection { fd: producer.into_raw_fd(), reader: MessageReader::new() },
+            consumer_ready: ConsumerReadySync::Binary(Vec::new()),
+            outstanding_frames: vec![0; buffer_count as usize],
+            released_frames: vec![0; buffer_count as usize],
+            resize_request: None,
+        });
+        
+        (handler, consumer)
+    }
+    
+    // Sends a frame from the buffer the way `signal_render_finished` does, with a
+    // stand-in for the sync fd, and returns the buffer index the consumer received
     #[cfg(unix)]
-    consumer_ready_semaphore_fd: Option<RawFd>,
-    #[cfg(not(unix))]
-    render_finished_semaphore_handle: Option<isize>,
-    #[cfg(not(unix))]
-    consumer_ready_semaphore_handle: Option<isize>,
-}
\ No newline at end of file
+    fn send_frame(handler: &mut IPCHandler, consumer: &UnixStream, buffer_index: usize) -> u32 {
+        let (sync_fd, _other) = UnixStream::pair().unwrap();
+        handler.send_frame_ready(buffer_index, 0, &[sync_fd.as_raw_fd()]);
+        
+        let mut message = MessageReader::new().read_message(consumer.as_raw_fd()).unwrap();
+        assert_eq!(message.fds.len(), 1);
+        message.close_fds();
+        message.decode::<FrameReady>().unwrap().buffer_index
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn single_buffer_frames_are_sent_once_released() {
+        let (handler, consumer) = binary_handler("single_buffer", 1);
+        let handler = Mutex::new(handler);
+        
+        for _ in 0..3 {
+            // Picks up the release of the previous frame
+            wait_for_release(&handler, 0, CONSUMER_WAIT_TIMEOUT);
+            
+            let mut locked = handler.lock().unwrap();
+            assert!(locked.is_buffer_free(0));
+            assert_eq!(send_frame(&mut locked, &consumer, 0), 0);
+            assert!(!locked.is_buffer_free(0));
+            drop(locked);
+            
+            send_message(consumer.as_raw_fd(), &BufferReleased { buffer_index: 0 }, &[]).unwrap();
+        }
+        wait_for_release(&handler, 0, CONSUMER_WAIT_TIMEOUT);
+        
+        let locked = handler.lock().unwrap();
+        assert!(locked.has_clients() && locked.is_buffer_free(0));
+        assert_eq!(locked.clients[0].released_frames, [3]);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn two_buffers_keep_flowing_to_a_copying_consumer() {
+        let (handler, consumer) = binary_handler("two_buffers", 2);
+        let handler = Mutex::new(handler);
+        
+        // Like `VulkanSharingConsumerPlugin`, which copies every frame received during
+        // an update and releases it once the copy has completed, an update later
+        let mut copied: Vec<u32> = Vec::new();
+        let mut received = Vec::new();
+        
+        for frame in 0..6 {
+            let buffer_index = frame % 2;
+            wait_for_release(&handler, buffer_index, CONSUMER_WAIT_TIMEOUT);
+            
+            let mut locked = handler.lock().unwrap();
+            assert!(locked.has_clients() && locked.is_buffer_free(buffer_index));
+            received.push(send_frame(&mut locked, &consumer, buffer_index));
+            drop(locked);
+            
+            for buffer_index in copied.drain(..) {
+                send_message(consumer.as_raw_fd(), &BufferReleased { buffer_index }, &[]).unwrap();
+            }
+            copied.extend(received.last());
+        }
+        
+        assert_eq!(received, [0, 1, 0, 1, 0, 1]);
+        assert!(handler.lock().unwrap().has_clients());
+    }
+}
@@ -0,0 +1,658 @@
+// Consumer side of the VulkanSharingPlugin socket. Connects to a producer, imports
+// the shared images into a Vulkan device owned by the caller and hands out frames
+// along with the semaphores that order access to them.
+//
+// The caller submits its own work: it waits on `AcquiredFrame::wait_semaphore`
+// before reading the image and signals `AcquiredFrame::signal_semaphore` in the
+// same or a later submission, then calls `release_frame`. In timeline mode both
+// semaphores are timelines used at `AcquiredFrame::frame_value`. In binary mode the
+// wait semaphore is created for the frame and destroyed when it is released.
+//
+// When the producer resizes, the images are imported again while acquiring. The
+// replaced ones stay valid for frames acquired before, until
+// `destroy_retired_images`.
+//
+// Producers sharing depth hand out a depth image per buffer too, written by the
+// same frame as the color image and synchronized along with it.
+
+use ash::vk;
+use bevy::render::render_resource::Extent3d;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use super::protocol::{
+    send_message, BufferReleased, Capabilities, FrameReady, Hello, MemoryHandleType, Message, MessageKind,
+    MessageReader, Metadata, Reject, ResizeRequest,
+};
+use super::FrameSyncMode;
+use crate::vulkan_interop::{import_dma_buf_image, import_image_memory_fd, ImportedImage};
+use crate::{ExternalSurfaceError, Result};
+
+#[derive(Debug, Clone)]
+pub struct VulkanSharingClientConfig {
+    pub socket_path: String,
+    // Shown in the producer's logs
+    pub name: String,
+    // Sync modes and memory the caller's device can take part in. Timeline mode
+    // needs the timelineSemaphore feature enabled on it, DMA-BUF memory the
+    // VK_EXT_external_memory_dma_buf and VK_EXT_image_drm_format_modifier extensions.
+    pub capabilities: Capabilities,
+}
+
+impl Default for VulkanSharingClientConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: "/tmp/bevy_vulkan_sharing.sock".to_string(),
+            name: "vulkan_sharing_client".to_string(),
+            capabilities: Capabilities::SYNC_FD | Capabilities::TIMELINE_SEMAPHORES,
+        }
+    }
+}
+
+// A frame the producer has submitted, valid until it is released
+#[derive(Debug, Clone, Copy)]
+pub struct AcquiredFrame {
+    pub buffer_index: u32,
+    // Value to wait on and signal in timeline mode, unused in binary mode
+    pub frame_value: u64,
+    pub image: vk::Image,
+    // Set when the producer shares depth
+    pub depth_image: Option<vk::Image>,
+    pub wait_semaphore: vk::Semaphore,
+    pub signal_semaphore: vk::Semaphore,
+}
+
+// A connection whose handshake is in progress, for callers that must not block
+// until the producer answers. `VulkanSharingClient::connect` does both steps.
+pub struct PendingConnection {
+    stream: UnixStream,
+    // Buffers the producer's `Metadata` or `Reject` as it arrives
+    reader: MessageReader,
+}
+
+impl PendingConnection {
+    // Connects and sends `Hello` without waiting for the answer
+    pub fn start(config: &VulkanSharingClientConfig) -> Result<Self> {
+        let stream = UnixStream::connect(&config.socket_path).map_err(|e| {
+            ExternalSurfaceError::SurfaceCreationFailed(format!("Failed to connect to {}: {}", config.socket_path, e))
+        })?;
+        
+        let hello = Hello {
+            capabilities: config.capabilities,
+            name: config.name.clone(),
+        };
+        send_message(stream.as_raw_fd(), &hello, &[])?;
+        
+        Ok(Self {
+            stream,
+            reader: MessageReader::new(),
+        })
+    }
+    
+    // Whether the producer has answered, never blocks
+    pub fn is_answered(&mut self) -> Result<bool> {
+        self.reader.poll_message(self.stream.as_raw_fd())
+    }
+    
+    // Completes the handshake, blocking until the producer answers unless
+    // `is_answered` returned true. `device` has the same requirements as for
+    // `VulkanSharingClient::connect`.
+    pub fn finish(
+        mut self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> Result<VulkanSharingClient> {
+        let answer = self.reader.read_message(self.stream.as_raw_fd())?;
+        
+        VulkanSharingClient::from_answer(self.stream, self.reader, answer, instance, physical_device, device)
+    }
+}
+
+// One buffer's imported images
+struct ClientBuffer {
+    image: ImportedImage,
+    depth_image: Option<ImportedImage>,
+}
+
+// Semaphores the producer's fds are imported into
+enum ClientSync {
+    // consumer_ready per buffer, imported once from the metadata. Each frame's
+    // render_finished sync fd gets a semaphore of its own.
+    Binary {
+        consumer_ready: Vec<vk::Semaphore>,
+    },
+    // Imported once from the metadata
+    Timeline {
+        render_finished: vk::Semaphore,
+        consumer_ready: vk::Semaphore,
+    },
+}
+
+pub struct VulkanSharingClient {
+    stream: UnixStream,
+    reader: MessageReader,
+    metadata: Metadata,
+    buffers: Vec<ClientBuffer>,
+    // Replaced by a resize, possibly still used by acquired frames
+    retired_buffers: Vec<ClientBuffer>,
+    // Frames acquired and not yet released
+    acquired_frames: Vec<AcquiredFrame>,
+    sync: ClientSync,
+    device: ash::Device,
+    mem_properties: vk::PhysicalDeviceMemoryProperties,
+    ext_memory_fd: ash::khr::external_memory_fd::Device,
+    ext_semaphore_fd: ash::khr::external_semaphore_fd::Device,
+}
+
+impl VulkanSharingClient {
+    // Connects and completes the handshake, blocking until the producer answers.
+    // `device` must have been created from `physical_device` with
+    // VK_KHR_external_memory_fd and VK_KHR_external_semaphore_fd enabled, plus the
+    // DMA-BUF extensions if `config.capabilities` has `DMA_BUF`, and has to outlive
+    // the client.
+    pub fn connect(
+        config: &VulkanSharingClientConfig,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> Result<Self> {
+        PendingConnection::start(config)?.finish(instance, physical_device, device)
+    }
+    
+    fn from_answer(
+        stream: UnixStream,
+        reader: MessageReader,
+        mut message: Message,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> Result<Self> {
+        if message.kind == MessageKind::Reject {
+            message.close_fds();
+            let reject: Reject = message.decode()?;
+            return Err(ExternalSurfaceError::IpcProtocolError(format!("Producer rejected connection: {}", reject.reason)));
+        }
+        
+        let metadata: Metadata = match message.decode() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                message.close_fds();
+                return Err(e);
+            }
+        };
+        
+        let mut client = Self {
+            stream,
+            reader,
+            metadata,
+            buffers: Vec::new(),
+            retired_buffers: Vec::new(),
+            acquired_frames: Vec::new(),
+            sync: ClientSync::Binary { consumer_ready: Vec::new() },
+            device: device.clone(),
+            mem_properties: unsafe { instance.get_physical_device_memory_properties(physical_device) },
+            ext_memory_fd: ash::khr::external_memory_fd::Device::new(instance, device),
+            ext_semaphore_fd: ash::khr::external_semaphore_fd::Device::new(instance, device),
+        };
+        
+        client.import_shared_fds(&mut message)?;
+        
+        Ok(client)
+    }
+    
+    // Memory fds come first in buffer order, then the depth ones, followed in binary
+    // mode by the consumer_ready semaphores and in timeline mode by the
+    // render_finished and consumer_ready timelines. Every fd in the message is
+    // consumed, whether imported or closed.
+    fn import_shared_fds(&mut self, message: &mut Message) -> Result<()> {
+        let memory_fd_count = self.memory_fd_count();
+        let expected = match self.metadata.sync_mode {
+            FrameSyncMode::Binary => memory_fd_count + self.metadata.buffer_count as usize,
+            FrameSyncMode::Timeline => memory_fd_count + 2,
+        };
+        if let Err(e) = self.check_shared_fds(message, expected) {
+            message.close_fds();
+            return Err(e);
+        }
+        
+        let mut fds = std::mem::take(&mut message.fds).into_iter();
+        let result = self.import_buffers(&mut fds)
+            .and_then(|buffers| {
+                self.buffers = buffers;
+                self.create_semaphores(&mut fds)
+            });
+        
+        // Left over if an import failed part way
+        for fd in fds {
+            unsafe { libc::close(fd) };
+        }
+        
+        result
+    }
+    
+    fn memory_fd_count(&self) -> usize {
+        let buffer_count = self.metadata.buffer_count as usize;
+        match self.metadata.depth_format {
+            Some(_) => buffer_count * 2,
+            None => buffer_count,
+        }
+    }
+    
+    fn check_shared_fds(&self, message: &Message, expected: usize) -> Result<()> {
+        let buffer_count = self.metadata.buffer_count as usize;
+        
+        if message.fds.len() != expected {
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                format!("Metadata carried {} fds, expected {}", message.fds.len(), expected),
+            ));
+        }
+        if self.metadata.memory_handle_type == MemoryHandleType::DmaBuf
+            && self.metadata.dma_buf_layouts.len() != buffer_count
+        {
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                format!("Metadata carried {} DMA-BUF layouts for {} buffers", self.metadata.dma_buf_layouts.len(), buffer_count),
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    // Imports the images of a resize, which come with only the memory fds. Consumes
+    // every fd in the message.
+    fn resize(&mut self, mut message: Message) -> Result<()> {
+        let metadata: Metadata = match message.decode() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                message.close_fds();
+                return Err(e);
+            }
+        };
+        
+        let unchanged = metadata.buffer_count == self.metadata.buffer_count
+            && metadata.format == self.metadata.format
+            && metadata.sync_mode == self.metadata.sync_mode
+            && metadata.memory_handle_type == self.metadata.memory_handle_type
+            && metadata.depth_format == self.metadata.depth_format;
+        if !unchanged {
+            message.close_fds();
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                "Resize changed more than the size of the shared images".into(),
+            ));
+        }
+        
+        let previous = std::mem::replace(&mut self.metadata, metadata);
+        if let Err(e) = self.check_shared_fds(&message, self.memory_fd_count()) {
+            message.close_fds();
+            self.metadata = previous;
+            return Err(e);
+        }
+        
+        let mut fds = std::mem::take(&mut message.fds).into_iter();
+        match self.import_buffers(&mut fds) {
+            Ok(buffers) => {
+                let replaced = std::mem::replace(&mut self.buffers, buffers);
+                self.retired_buffers.extend(replaced);
+                Ok(())
+            }
+            Err(e) => {
+                for fd in fds {
+                    unsafe { libc::close(fd) };
+                }
+                self.metadata = previous;
+                Err(e)
+            }
+        }
+    }
+    
+    fn import_buffers(&self, fds: &mut impl Iterator<Item = RawFd>) -> Result<Vec<ClientBuffer>> {
+        let images = self.import_images(fds)?;
+        
+        let Some(depth_format) = self.depth_format() else {
+            return Ok(images.into_iter().map(|image| ClientBuffer { image, depth_image: None }).collect());
+        };
+        
+        // Depth is always shared as opaque fds
+        let size = self.size();
+        let mut depth_images = Vec::new();
+        for fd in fds.take(self.metadata.buffer_count as usize) {
+            depth_images.push(unsafe {
+                import_image_memory_fd(&self.device, &self.mem_properties, fd, size, depth_format)
+            }?);
+        }
+        
+        Ok(images.into_iter()
+            .zip(depth_images)
+            .map(|(image, depth_image)| ClientBuffer { image, depth_image: Some(depth_image) })
+            .collect())
+    }
+    
+    fn size(&self) -> Extent3d {
+        Extent3d {
+            width: self.metadata.width,
+            height: self.metadata.height,
+            depth_or_array_layers: 1,
+        }
+    }
+    
+    fn import_images(&self, fds: &mut impl Iterator<Item = RawFd>) -> Result<Vec<ImportedImage>> {
+        let size = self.size();
+        let format = self.format();
+        let mut images = Vec::new();
+        
+        for (buffer_index, fd) in fds.take(self.metadata.buffer_count as usize).enumerate() {
+            let image = match self.metadata.memory_handle_type {
+                MemoryHandleType::OpaqueFd => unsafe {
+                    import_image_memory_fd(&self.device, &self.mem_properties, fd, size, format)
+                },
+                MemoryHandleType::DmaBuf => {
+                    // The image has to be created with the producer's exact layout
+                    let layout = &self.metadata.dma_buf_layouts[buffer_index];
+                    let planes: Vec<vk::SubresourceLayout> = layout.planes.iter()
+                        .map(|plane| vk::SubresourceLayout {
+                            offset: plane.offset,
+                            row_pitch: plane.stride,
+                            ..Default::default()
+                        })
+                        .collect();
+                    
+                    unsafe {
+                        import_dma_buf_image(
+                            &self.device,
+                            &self.ext_memory_fd,
+                            &self.mem_properties,
+                            fd,
+                            size,
+                            format,
+                            layout.modifier,
+                            &planes,
+                        )
+                    }
+                }
+            }?;
+            images.push(image);
+        }
+        
+        Ok(images)
+    }
+    
+    fn create_semaphores(&mut self, fds: &mut impl Iterator<Item = RawFd>) -> Result<()> {
+        match self.metadata.sync_mode {
+            FrameSyncMode::Binary => {
+                let mut consumer_ready = Vec::new();
+                for fd in fds.take(self.metadata.buffer_count as usize) {
+                    match self.import_semaphore(fd, vk::SemaphoreType::BINARY) {
+                        Ok(semaphore) => consumer_ready.push(semaphore),
+                        Err(e) => {
+                            for semaphore in consumer_ready {
+                                unsafe { self.device.destroy_semaphore(semaphore, None) };
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                
+                self.sync = ClientSync::Binary { consumer_ready };
+            }
+            FrameSyncMode::Timeline => {
+                let (Some(render_finished_fd), Some(consumer_ready_fd)) = (fds.next(), fds.next()) else {
+                    return Err(ExternalSurfaceError::IpcProtocolError("Missing timeline semaphore fds".into()));
+                };
+                
+                let render_finished = self.import_semaphore(render_finished_fd, vk::SemaphoreType::TIMELINE);
+                let consumer_ready = self.import_semaphore(consumer_ready_fd, vk::SemaphoreType::TIMELINE);
+                
+                match (render_finished, consumer_ready) {
+                    (Ok(render_finished), Ok(consumer_ready)) => {
+                        self.sync = ClientSync::Timeline { render_finished, consumer_ready };
+                    }
+                    (render_finished, consumer_ready) => {
+                        for &semaphore in [&render_finished, &consumer_ready].into_iter().flatten() {
+                            unsafe { self.device.destroy_semaphore(semaphore, None) };
+                        }
+                        return render_finished.and(consumer_ready).map(|_| ());
+                    }
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Creates a semaphore sharing the payload of the opaque `fd`, taking ownership
+    // of it
+    fn import_semaphore(&self, fd: RawFd, semaphore_type: vk::SemaphoreType) -> Result<vk::Semaphore> {
+        let semaphore = match create_semaphore(&self.device, semaphore_type) {
+            Ok(semaphore) => semaphore,
+            Err(e) => {
+                unsafe { libc::close(fd) };
+                return Err(e);
+            }
+        };
+        
+        match self.import_semaphore_fd(semaphore, fd, vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD) {
+            Ok(()) => Ok(semaphore),
+            Err(e) => {
+                unsafe { self.device.destroy_semaphore(semaphore, None) };
+                Err(e)
+            }
+        }
+    }
+    
+    // Takes ownership of `fd`. Sync fds are imported temporarily and only apply to
+    // the next wait.
+    fn import_semaphore_fd(
+        &self,
+        semaphore: vk::Semaphore,
+        fd: RawFd,
+        handle_type: vk::ExternalSemaphoreHandleTypeFlags,
+    ) -> Result<()> {
+        let flags = if handle_type == vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD {
+            vk::SemaphoreImportFlags::TEMPORARY
+        } else {
+            vk::SemaphoreImportFlags::empty()
+        };
+        
+        let import_info = vk::ImportSemaphoreFdInfoKHR::default()
+            .semaphore(semaphore)
+            .flags(flags)
+            .handle_type(handle_type)
+            .fd(fd);
+        
+        unsafe { self.ext_semaphore_fd.import_semaphore_fd(&import_info) }.map_err(|e| {
+            unsafe { libc::close(fd) };
+            ExternalSurfaceError::SynchronizationFailed(format!("Failed to import semaphore fd: {:?}", e))
+        })
+    }
+    
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+    
+    pub fn format(&self) -> vk::Format {
+        vk::Format::from_raw(self.metadata.format as i32)
+    }
+    
+    pub fn depth_format(&self) -> Option<vk::Format> {
+        self.metadata.depth_format.map(|format| vk::Format::from_raw(format as i32))
+    }
+    
+    // The imported images, in buffer order
+    pub fn images(&self) -> Vec<vk::Image> {
+        self.buffers.iter().map(|buffer| buffer.image.image).collect()
+    }
+    
+    // The imported depth images in buffer order, empty unless the producer shares
+    // depth
+    pub fn depth_images(&self) -> Vec<vk::Image> {
+        self.buffers.iter().filter_map(|buffer| buffer.depth_image.as_ref()).map(|image| image.image).collect()
+    }
+    
+    // Readable whenever a message from the producer has arrived, for use with
+    // poll or an event loop
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+    
+    // Blocks until the producer announces its next frame
+    pub fn acquire_frame(&mut self) -> Result<AcquiredFrame> {
+        loop {
+            let message = self.reader.read_message(self.stream.as_raw_fd())?;
+            
+            if message.kind == MessageKind::Metadata {
+                self.resize(message)?;
+                continue;
+            }
+            return self.frame_from_message(message);
+        }
+    }
+    
+    // Returns the next frame if the producer has announced one, never blocks
+    pub fn try_acquire_frame(&mut self) -> Result<Option<AcquiredFrame>> {
+        while let Some(message) = self.reader.try_read_message(self.stream.as_raw_fd())? {
+            if message.kind == MessageKind::Metadata {
+                self.resize(message)?;
+                continue;
+            }
+            return self.frame_from_message(message).map(Some);
+        }
+        
+        Ok(None)
+    }
+    
+    // Asks the producer to reallocate the shared images. The new ones arrive with a
+    // later frame, `metadata` and `images` change once they have been imported.
+    pub fn request_resize(&mut self, width: u32, height: u32) -> Result<()> {
+        send_message(self.stream.as_raw_fd(), &ResizeRequest { width, height }, &[])
+    }
+    
+    // Frees the images replaced by resizes that no unreleased frame uses anymore.
+    // The caller has to make sure its submissions using them have completed.
+    pub fn destroy_retired_images(&mut self) {
+        let acquired_frames = &self.acquired_frames;
+        self.retired_buffers.retain(|buffer| acquired_frames.iter().any(|frame| frame.image == buffer.image.image));
+    }
+    
+    fn frame_from_message(&mut self, mut message: Message) -> Result<AcquiredFrame> {
+        let frame = match message.decode::<FrameReady>() {
+            Ok(frame) => frame,
+            Err(e) => {
+                message.close_fds();
+                return Err(e);
+            }
+        };
+        
+        let buffer_index = frame.buffer_index as usize;
+        let Some((image, depth_image)) = self.buffers.get(buffer_index)
+            .map(|buffer| (buffer.image.image, buffer.depth_image.as_ref().map(|image| image.image)))
+        else {
+            message.close_fds();
+            return Err(ExternalSurfaceError::IpcProtocolError(format!("Frame for unknown buffer {}", buffer_index)));
+        };
+        
+        let (wait_semaphore, signal_semaphore) = match &self.sync {
+            ClientSync::Binary { consumer_ready } => {
+                let [render_finished_fd] = message.fds[..] else {
+                    let received = message.fds.len();
+                    message.close_fds();
+                    return Err(ExternalSurfaceError::IpcProtocolError(
+                        format!("Binary mode frame carried {} fds, expected 1", received),
+                    ));
+                };
+                message.fds.clear();
+                
+                let signal_semaphore = consumer_ready[buffer_index];
+                
+                // A semaphore of its own, so the import never replaces one a wait may
+                // still be pending on
+                let wait_semaphore = match create_semaphore(&self.device, vk::SemaphoreType::BINARY) {
+                    Ok(semaphore) => semaphore,
+                    Err(e) => {
+                        unsafe { libc::close(render_finished_fd) };
+                        return Err(e);
+                    }
+                };
+                let imported = self.import_semaphore_fd(
+                    wait_semaphore,
+                    render_finished_fd,
+                    vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD,
+                );
+                if let Err(e) = imported {
+                    unsafe { self.device.destroy_semaphore(wait_semaphore, None) };
+                    return Err(e);
+                }
+                
+                (wait_semaphore, signal_semaphore)
+            }
+            ClientSync::Timeline { render_finished, consumer_ready } => {
+                message.close_fds();
+                (*render_finished, *consumer_ready)
+            }
+        };
+        
+        let frame = AcquiredFrame {
+            buffer_index: frame.buffer_index,
+            frame_value: frame.frame_value,
+            image,
+            depth_image,
+            wait_semaphore,
+            signal_semaphore,
+        };
+        self.acquired_frames.push(frame);
+        
+        Ok(frame)
+    }
+    
+    // Hands the buffer back to the producer. The signal of `signal_semaphore` must
+    // have been submitted already. In binary mode `wait_semaphore` is destroyed, so
+    // the submission waiting on it must have completed.
+    pub fn release_frame(&mut self, frame: AcquiredFrame) -> Result<()> {
+        let position = self.acquired_frames.iter().position(|acquired| {
+            (acquired.image, acquired.frame_value, acquired.wait_semaphore)
+                == (frame.image, frame.frame_value, frame.wait_semaphore)
+        });
+        if let Some(position) = position {
+            self.acquired_frames.swap_remove(position);
+            
+            if let ClientSync::Binary { .. } = self.sync {
+                unsafe { self.device.destroy_semaphore(frame.wait_semaphore, None) };
+            }
+        }
+        
+        let released = BufferReleased {
+            buffer_index: frame.buffer_index,
+        };
+        
+        send_message(self.stream.as_raw_fd(), &released, &[])
+    }
+}
+
+// The caller has to make sure none of its submissions still use the images or
+// semaphores
+impl Drop for VulkanSharingClient {
+    fn drop(&mut self) {
+        let semaphores = match &self.sync {
+            ClientSync::Binary { consumer_ready } => {
+                let wait_semaphores = self.acquired_frames.iter().map(|frame| frame.wait_semaphore);
+                consumer_ready.iter().copied().chain(wait_semaphores).collect()
+            }
+            ClientSync::Timeline { render_finished, consumer_ready } => vec![*render_finished, *consumer_ready],
+        };
+        
+        unsafe {
+            for semaphore in semaphores {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+        }
+    }
+}
+
+fn create_semaphore(device: &ash::Device, semaphore_type: vk::SemaphoreType) -> Result<vk::Semaphore> {
+    let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+        .semaphore_type(semaphore_type)
+        .initial_value(0);
+    let create_info = vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+    
+    unsafe { device.create_semaphore(&create_info, None) }
+        .map_err(|e| ExternalSurfaceError::SynchronizationFailed(format!("Failed to create semaphore: {:?}", e)))
+}
@@ -0,0 +1,599 @@
+// Bevy side of a VulkanSharingClient: displays frames shared by a
+// `VulkanSharingPlugin` running in another process.
+//
+// The main world connects without ever blocking the frame, polling the handshake
+// across updates, then acquires frames every update and exposes the latest one
+// through a single `Handle<Image>`. The image asset only lives in the main
+// world, the render world points its `GpuImage` at a texture of its own.
+//
+// Every render, the latest frame is copied into that texture. The GPU waits for
+// the frame before the copy and signals every received frame after it, both
+// submitted to Bevy's queue from an exclusive render world system. Frames are
+// released once that signal has completed, so the producer gets every buffer back
+// within a frame or two, however few it has.
+//
+// When the producer resizes, the image asset and the texture take the new size
+// and the replaced buffers are freed once no frame from them is copied anymore.
+
+use ash::vk;
+use bevy::{
+    log::{info, warn},
+    prelude::*,
+    render::{
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_resource::{DefaultImageSampler, Extent3d, TextureDescriptor, TextureDimension, TextureUsages},
+        renderer::{render_system, RenderDevice, RenderQueue},
+        texture::GpuImage,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wgpu_hal::api::Vulkan as VulkanApi;
+
+use super::client::{AcquiredFrame, PendingConnection, VulkanSharingClient, VulkanSharingClientConfig};
+use super::protocol::Capabilities;
+use super::{wrap_shared_image, FrameSyncMode};
+use crate::vulkan_interop::{require_device_extension, submit_semaphores, vk_format_to_wgpu};
+use crate::{ExternalSurfaceError, Result};
+
+// How long a producer has to answer the handshake before connecting is retried
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct VulkanSharingConsumerConfig {
+    pub client: VulkanSharingClientConfig,
+    // How often to try connecting while no producer is attached
+    pub reconnect_interval: Duration,
+}
+
+impl Default for VulkanSharingConsumerConfig {
+    fn default() -> Self {
+        Self {
+            client: VulkanSharingClientConfig {
+                name: "vulkan_sharing_consumer_plugin".to_string(),
+                ..Default::default()
+            },
+            reconnect_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+// Sent whenever the image starts showing a newer frame
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SharedFrameReceived {
+    pub buffer_index: u32,
+    pub frame_value: u64,
+}
+
+#[derive(Resource, Clone)]
+pub struct SharedFrameReceiver {
+    pub config: VulkanSharingConsumerConfig,
+    // Shows the latest frame once connected. Sprites and UI nodes pick up every new
+    // frame, materials cache their bind groups and have to be marked changed on
+    // `SharedFrameReceived`.
+    pub image: Handle<Image>,
+    connection: Option<Arc<Mutex<ReceiverConnection>>>,
+    // Frames received during one update, oldest first
+    received_frames: Vec<AcquiredFrame>,
+    last_connect_attempt: Option<Instant>,
+}
+
+impl SharedFrameReceiver {
+    fn new(config: VulkanSharingConsumerConfig) -> Self {
+        Self {
+            config,
+            image: Handle::default(),
+            connection: None,
+            received_frames: Vec::new(),
+            last_connect_attempt: None,
+        }
+    }
+    
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+    
+    // Asks the producer to share frames of a different size, for example to follow
+    // the size the image is displayed at
+    pub fn request_resize(&self, width: u32, height: u32) -> Result<()> {
+        let Some(connection) = &self.connection else {
+            return Err(ExternalSurfaceError::IpcDisconnected);
+        };
+        let Ok(mut connection) = connection.lock() else {
+            return Err(ExternalSurfaceError::IpcDisconnected);
+        };
+        
+        connection.client.request_resize(width, height)
+    }
+}
+
+// A connected client, the imported images wrapped for wgpu, and the frames the
+// render world still holds
+struct ReceiverConnection {
+    client: VulkanSharingClient,
+    format: wgpu::TextureFormat,
+    // Current images first, then replaced ones still copied from
+    textures: Vec<(vk::Image, wgpu::Texture)>,
+    // Holds a copy of the latest frame, created at the size frames are shared at
+    local: Option<GpuImage>,
+    device: ash::Device,
+    // wgpu's queue, which frame waits and signals are submitted to
+    queue: vk::Queue,
+    // Keeps the device alive until the imported images are destroyed
+    render_device: RenderDevice,
+    // Frames received since the last copy, in frame order
+    to_copy: Vec<AcquiredFrame>,
+    // Frames whose signal has been submitted, in frame order, along with the flag
+    // set once it has completed
+    releasing: Vec<(Vec<AcquiredFrame>, Arc<AtomicBool>)>,
+    // Set by the render world once submitting a wait or signal failed
+    failed: bool,
+}
+
+impl ReceiverConnection {
+    fn connect(
+        pending: PendingConnection,
+        config: &VulkanSharingClientConfig,
+        render_device: &RenderDevice,
+    ) -> Result<Self> {
+        let connected = unsafe {
+            render_device.wgpu_device().as_hal::<VulkanApi, _, Result<(VulkanSharingClient, ash::Device, vk::Queue)>>(|hal_device| {
+                let hal_device = hal_device.ok_or_else(|| {
+                    ExternalSurfaceError::UnsupportedBackend("Not using Vulkan backend".into())
+                })?;
+                
+                require_device_extension(hal_device, ash::khr::external_memory_fd::NAME)?;
+                require_device_extension(hal_device, ash::khr::external_semaphore_fd::NAME)?;
+                if config.capabilities.contains(Capabilities::DMA_BUF) {
+                    require_device_extension(hal_device, ash::ext::external_memory_dma_buf::NAME)?;
+                    require_device_extension(hal_device, ash::ext::image_drm_format_modifier::NAME)?;
+                }
+                
+                let client = pending.finish(
+                    hal_device.shared_instance().raw_instance(),
+                    hal_device.raw_physical_device(),
+                    hal_device.raw_device(),
+                )?;
+                
+                Ok((client, hal_device.raw_device().clone(), hal_device.raw_queue()))
+            })
+        };
+        let (client, device, queue) = connected?;
+        let format = vk_format_to_wgpu(client.format()).ok_or_else(|| {
+            warn!("Producer shares frames in {:?}, which wgpu cannot sample", client.format());
+            ExternalSurfaceError::InvalidTextureFormat
+        })?;
+        
+        let mut connection = Self {
+            client,
+            format,
+            textures: Vec::new(),
+            local: None,
+            device,
+            queue,
+            render_device: render_device.clone(),
+            to_copy: Vec::new(),
+            releasing: Vec::new(),
+            failed: false,
+        };
+        connection.update_textures(render_device);
+        
+        Ok(connection)
+    }
+    
+    // Wraps the client's images that have no texture yet, which after connecting
+    // and after every resize is all of them. Returns whether there were any.
+    fn update_textures(&mut self, render_device: &RenderDevice) -> bool {
+        let metadata = self.client.metadata();
+        let format = self.format;
+        let size = Extent3d {
+            width: metadata.width,
+            height: metadata.height,
+            depth_or_array_layers: 1,
+        };
+        
+        let mut textures = Vec::new();
+        let mut created = false;
+        for (i, vk_image) in self.client.images().into_iter().enumerate() {
+            if let Some(position) = self.textures.iter().position(|(image, _)| *image == vk_image) {
+                textures.push(self.textures.remove(position));
+                continue;
+            }
+            
+            let texture = unsafe { wrap_shared_image(
+                render_device,
+                vk_image,
+                size.width,
+                size.height,
+                format,
+                &format!("received_texture_{}", i),
+            ) };
+            created = true;
+            
+            textures.push((vk_image, texture));
+        }
+        
+        // Whatever is left was replaced, but may still be copied from
+        textures.append(&mut self.textures);
+        self.textures = textures;
+        
+        created
+    }
+    
+    fn texture(&self, image: vk::Image) -> Option<&wgpu::Texture> {
+        self.textures.iter().find(|(texture_image, _)| *texture_image == image).map(|(_, texture)| texture)
+    }
+    
+    // Returns the texture frames are copied into, replacing it once frames are
+    // shared at a different size
+    fn local_texture(&mut self, render_device: &RenderDevice, default_sampler: &DefaultImageSampler) -> &GpuImage {
+        let metadata = self.client.metadata();
+        let size = Extent3d {
+            width: metadata.width,
+            height: metadata.height,
+            depth_or_array_layers: 1,
+        };
+        
+        if self.local.as_ref().is_none_or(|local| local.size != size) {
+            let texture = render_device.create_texture(&TextureDescriptor {
+                label: Some("shared_frame_copy"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            
+            self.local = Some(GpuImage {
+                texture,
+                texture_view,
+                texture_format: self.format,
+                sampler: (**default_sampler).clone(),
+                size,
+                mip_level_count: 1,
+            });
+        }
+        
+        self.local.as_ref().unwrap()
+    }
+    
+    // Drops the replaced images no frame uses anymore. Frames are only released once
+    // the copies from them have completed, so unreleased ones cover every use.
+    fn retire_textures(&mut self) {
+        self.client.destroy_retired_images();
+        
+        let current = self.client.images();
+        let in_use: Vec<vk::Image> = self.to_copy.iter()
+            .chain(self.releasing.iter().flat_map(|(frames, _)| frames))
+            .map(|frame| frame.image)
+            .collect();
+        self.textures.retain(|(image, _)| current.contains(image) || in_use.contains(image));
+    }
+    
+    // Stand-in for the frame's image in the main world, which only needs the size
+    // and format
+    fn placeholder_image(&self) -> Image {
+        let metadata = self.client.metadata();
+        
+        Image {
+            data: None,
+            texture_descriptor: TextureDescriptor {
+                label: Some("shared_frame"),
+                size: Extent3d {
+                    width: metadata.width,
+                    height: metadata.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            // Never extracted, the render world uses the imported textures instead
+            asset_usage: RenderAssetUsages::MAIN_WORLD,
+            ..default()
+        }
+    }
+    
+    // Copies the latest frame received into the local texture and hands every
+    // received frame back once the copy has completed. Called from an exclusive
+    // render world system, see `ExternalSemaphore::queue_wait`.
+    unsafe fn copy_frames(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) -> Result<()> {
+        let frames = std::mem::take(&mut self.to_copy);
+        
+        if let Some(latest) = frames.last() {
+            // Frames from before a resize no longer fit and are only released
+            let source = self.texture(latest.image)
+                .zip(self.local.as_ref())
+                .filter(|(texture, local)| texture.size() == local.texture.size())
+                .map(|(texture, local)| (texture.clone(), local.texture.clone()));
+            
+            if let Some((texture, local)) = source {
+                let wait = [(latest.wait_semaphore, self.timeline_value(latest))];
+                unsafe { submit_semaphores(&self.device, self.queue, &wait, &[]) }?;
+                
+                let mut encoder = render_device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("shared_frame_copy"),
+                });
+                encoder.copy_texture_to_texture(texture.as_image_copy(), local.as_image_copy(), texture.size());
+                render_queue.submit([encoder.finish()]);
+            }
+            
+            // A timeline signal covers the earlier frame values
+            let signals: Vec<_> = match self.client.metadata().sync_mode {
+                FrameSyncMode::Binary => frames.iter().map(|frame| (frame.signal_semaphore, None)).collect(),
+                FrameSyncMode::Timeline => vec![(latest.signal_semaphore, Some(latest.frame_value))],
+            };
+            unsafe { submit_semaphores(&self.device, self.queue, &[], &signals) }?;
+            
+            // Makes wgpu's submissions cover the signal, so waiting for wgpu covers it
+            render_queue.submit(std::iter::empty());
+            
+            let completed = Arc::new(AtomicBool::new(false));
+            let flag = completed.clone();
+            render_queue.on_submitted_work_done(move || flag.store(true, Ordering::Release));
+            
+            self.releasing.push((frames, completed));
+        }
+        
+        // Releasing destroys binary mode wait semaphores, the waits have completed
+        // along with the signals
+        while let Some((_, completed)) = self.releasing.first()
+            && completed.load(Ordering::Acquire)
+        {
+            let (frames, _) = self.releasing.remove(0);
+            for frame in frames {
+                self.client.release_frame(frame)?;
+            }
+        }
+        
+        self.retire_textures();
+        Ok(())
+    }
+    
+    fn timeline_value(&self, frame: &AcquiredFrame) -> Option<u64> {
+        (self.client.metadata().sync_mode == FrameSyncMode::Timeline).then_some(frame.frame_value)
+    }
+}
+
+impl Drop for ReceiverConnection {
+    fn drop(&mut self) {
+        // The imported images go away with the client, and earlier frames may still
+        // be copying from them
+        self.render_device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+// Render world copy of what the main world received this update
+#[derive(Resource, Default)]
+struct ExtractedSharedFrames {
+    image: AssetId<Image>,
+    connection: Option<Arc<Mutex<ReceiverConnection>>>,
+    received_frames: Vec<AcquiredFrame>,
+}
+
+#[derive(Default)]
+pub struct VulkanSharingConsumerPlugin {
+    pub config: VulkanSharingConsumerConfig,
+}
+
+impl Plugin for VulkanSharingConsumerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SharedFrameReceiver::new(self.config.clone()));
+        app.add_event::<SharedFrameReceived>();
+        
+        // Frames are picked up before game code runs, so it sees the same image the
+        // render world will show
+        app.add_systems(PreUpdate, receive_shared_frames);
+        
+        let render_app = app.sub_app_mut(RenderApp);
+        
+        render_app
+            .init_resource::<ExtractedSharedFrames>()
+            .add_systems(ExtractSchedule, extract_shared_frames)
+            .add_systems(
+                Render,
+                (
+                    prepare_shared_frame.in_set(RenderSet::PrepareResources),
+                    // Exclusive, as nothing else may use the queue while it submits to it
+                    copy_shared_frames.in_set(RenderSet::Render).before(render_system),
+                ),
+            );
+    }
+    
+    // Image assets exist once every plugin is built. Creating the handle here makes
+    // it available to Startup systems.
+    fn finish(&self, app: &mut App) {
+        let image = app.world_mut().resource_mut::<Assets<Image>>().add(empty_image());
+        app.world_mut().resource_mut::<SharedFrameReceiver>().image = image;
+    }
+}
+
+// Shown until a producer is connected
+fn empty_image() -> Image {
+    Image {
+        data: None,
+        asset_usage: RenderAssetUsages::MAIN_WORLD,
+        ..default()
+    }
+}
+
+// Starts connecting every `reconnect_interval` and completes the handshake once
+// the producer has answered, so a producer that is slow to answer never stalls
+// the frame
+fn poll_connection(
+    receiver: &mut SharedFrameReceiver,
+    pending: &mut Option<PendingConnection>,
+    render_device: &RenderDevice,
+) -> Option<ReceiverConnection> {
+    if pending.is_none() {
+        let due = receiver.last_connect_attempt
+            .is_none_or(|attempt| attempt.elapsed() >= receiver.config.reconnect_interval);
+        if !due {
+            return None;
+        }
+        receiver.last_connect_attempt = Some(Instant::now());
+        
+        match PendingConnection::start(&receiver.config.client) {
+            Ok(started) => *pending = Some(started),
+            Err(e) => {
+                debug!("Failed to connect to producer: {}", e);
+                return None;
+            }
+        }
+    }
+    
+    let answered = pending.as_mut()?.is_answered();
+    let connected = match answered {
+        Ok(true) => {
+            let started = pending.take()?;
+            ReceiverConnection::connect(started, &receiver.config.client, render_device)
+        }
+        Ok(false) => {
+            if receiver.last_connect_attempt.is_some_and(|attempt| attempt.elapsed() >= HANDSHAKE_TIMEOUT) {
+                debug!("Producer did not answer within {:?}", HANDSHAKE_TIMEOUT);
+                *pending = None;
+            }
+            return None;
+        }
+        Err(e) => {
+            *pending = None;
+            Err(e)
+        }
+    };
+    
+    connected.map_err(|e| debug!("Failed to connect to producer: {}", e)).ok()
+}
+
+fn receive_shared_frames(
+    mut receiver: ResMut<SharedFrameReceiver>,
+    mut images: ResMut<Assets<Image>>,
+    render_device: Res<RenderDevice>,
+    mut received_events: EventWriter<SharedFrameReceived>,
+    mut pending: Local<Option<PendingConnection>>,
+) {
+    let receiver = receiver.as_mut();
+    receiver.received_frames.clear();
+    
+    if receiver.connection.is_none() {
+        let Some(connection) = poll_connection(receiver, &mut pending, &render_device) else {
+            return;
+        };
+        
+        info!(
+            "Connected to producer at {} as consumer {}",
+            receiver.config.client.socket_path,
+            connection.client.metadata().consumer_id,
+        );
+        
+        images.insert(&receiver.image, connection.placeholder_image());
+        receiver.connection = Some(Arc::new(Mutex::new(connection)));
+    }
+    
+    let Some(connection) = receiver.connection.clone() else {
+        return;
+    };
+    let Ok(mut connection) = connection.lock() else {
+        return;
+    };
+    
+    // The render world could not copy a frame
+    let disconnected = connection.failed || loop {
+        match connection.client.try_acquire_frame() {
+            Ok(Some(frame)) => receiver.received_frames.push(frame),
+            Ok(None) => break false,
+            Err(ExternalSurfaceError::IpcDisconnected) => break true,
+            Err(e) => {
+                warn!("Dropping connection to producer: {}", e);
+                break true;
+            }
+        }
+    };
+    
+    // The producer resized while acquiring
+    if !disconnected && connection.update_textures(&render_device) {
+        let metadata = connection.client.metadata();
+        info!("Producer resized shared frames to {}x{}", metadata.width, metadata.height);
+        
+        images.insert(&receiver.image, connection.placeholder_image());
+    }
+    drop(connection);
+    
+    if let Some(latest) = receiver.received_frames.last() {
+        // Sprites and UI nodes rebuild their bind groups for modified images
+        images.get_mut(&receiver.image);
+        received_events.write(SharedFrameReceived {
+            buffer_index: latest.buffer_index,
+            frame_value: latest.frame_value,
+        });
+    }
+    
+    if disconnected {
+        info!("Producer disconnected");
+        
+        // Frames received this update are never copied or released
+        receiver.received_frames.clear();
+        receiver.connection = None;
+        images.insert(&receiver.image, empty_image());
+    }
+}
+
+fn extract_shared_frames(
+    receiver: Extract<Res<SharedFrameReceiver>>,
+    mut extracted: ResMut<ExtractedSharedFrames>,
+) {
+    extracted.image = receiver.image.id();
+    extracted.connection = receiver.connection.clone();
+    extracted.received_frames = receiver.received_frames.clone();
+}
+
+// Points the image at the texture holding the latest frame and hands the frames
+// received this update over to `copy_shared_frames`
+fn prepare_shared_frame(
+    extracted: Res<ExtractedSharedFrames>,
+    render_device: Res<RenderDevice>,
+    default_sampler: Res<DefaultImageSampler>,
+    mut gpu_images: ResMut<RenderAssets<GpuImage>>,
+) {
+    let Some(connection) = &extracted.connection else {
+        gpu_images.remove(extracted.image);
+        return;
+    };
+    let Ok(mut connection) = connection.lock() else {
+        return;
+    };
+    
+    connection.to_copy.extend_from_slice(&extracted.received_frames);
+    
+    // Nothing to show until the first frame arrives
+    if connection.local.is_some() || !connection.to_copy.is_empty() {
+        let local = connection.local_texture(&render_device, &default_sampler);
+        gpu_images.insert(extracted.image, local.clone());
+    }
+}
+
+fn copy_shared_frames(world: &mut World) {
+    let extracted = world.resource::<ExtractedSharedFrames>();
+    let render_device = world.resource::<RenderDevice>();
+    let render_queue = world.resource::<RenderQueue>();
+    
+    let Some(connection) = &extracted.connection else {
+        return;
+    };
+    let Ok(mut connection) = connection.lock() else {
+        return;
+    };
+    
+    // Exclusive system, nothing else uses the queue
+    if let Err(e) = unsafe { connection.copy_frames(render_device, render_queue) } {
+        error!("Dropping connection to producer: {}", e);
+        connection.failed = true;
+    }
+}
@@ -0,0 +1,686 @@
+// Wire protocol spoken over the VulkanSharingPlugin socket, shared by producers and
+// consumers.
+//
+// Every message is a fixed 16 byte header followed by a bincode payload:
+//
+//   magic     [u8; 4]  b"BVKS"
+//   version   u16 LE   PROTOCOL_VERSION
+//   kind      u16 LE   MessageKind
+//   fd_count  u16 LE   file descriptors attached to the message
+//   reserved  u16      zero
+//   length    u32 LE   payload bytes following the header
+//
+// File descriptors travel as SCM_RIGHTS on the sendmsg carrying the message, so they
+// arrive with its first byte and are matched up with messages by fd_count.
+//
+// A connection starts with the consumer sending `Hello`. The producer answers with
+// `Metadata` if it can serve the consumer and `Reject` otherwise. After that the
+// producer sends `FrameReady` for every frame and the consumer answers each one it
+// is done with by `BufferReleased`.
+//
+// Either side may resize the shared images: the consumer asks with `ResizeRequest`,
+// and the producer sends `Metadata` again once the new images exist. Frames sent
+// before it refer to the old images.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(unix)]
+use nix::sys::socket::{self, ControlMessageOwned, MsgFlags};
+#[cfg(unix)]
+use std::collections::VecDeque;
+#[cfg(unix)]
+use std::os::fd::RawFd;
+
+use super::{ColorSpace, FrameSyncMode, MAX_BUFFER_COUNT};
+use crate::{ExternalSurfaceError, Result};
+
+pub const MAGIC: [u8; 4] = *b"BVKS";
+pub const PROTOCOL_VERSION: u16 = 3;
+pub const HEADER_SIZE: usize = 16;
+
+// Upper bounds a receiver enforces before trusting a header. The most fds are
+// carried by the first `Metadata` of a surface sharing depth in binary mode: a
+// color and a depth memory fd plus a consumer_ready semaphore per buffer.
+pub const MAX_PAYLOAD_SIZE: u32 = 64 * 1024;
+pub const MAX_FDS_PER_MESSAGE: usize = 3 * MAX_BUFFER_COUNT as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum MessageKind {
+    Hello = 1,
+    Metadata = 2,
+    Reject = 3,
+    FrameReady = 4,
+    BufferReleased = 5,
+    ResizeRequest = 6,
+}
+
+impl MessageKind {
+    fn from_raw(raw: u16) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Hello),
+            2 => Some(Self::Metadata),
+            3 => Some(Self::Reject),
+            4 => Some(Self::FrameReady),
+            5 => Some(Self::BufferReleased),
+            6 => Some(Self::ResizeRequest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub version: u16,
+    pub kind: MessageKind,
+    pub fd_count: u16,
+    pub length: u32,
+}
+
+impl MessageHeader {
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6..8].copy_from_slice(&(self.kind as u16).to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.fd_count.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.length.to_le_bytes());
+        bytes
+    }
+    
+    pub fn decode(bytes: &[u8; HEADER_SIZE]) -> Result<Self> {
+        if bytes[0..4] != MAGIC {
+            return Err(ExternalSurfaceError::IpcProtocolError("Bad message magic".into()));
+        }
+        
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != PROTOCOL_VERSION {
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                format!("Unsupported protocol version {} (expected {})", version, PROTOCOL_VERSION),
+            ));
+        }
+        
+        let raw_kind = u16::from_le_bytes([bytes[6], bytes[7]]);
+        let kind = MessageKind::from_raw(raw_kind).ok_or_else(|| {
+            ExternalSurfaceError::IpcProtocolError(format!("Unknown message kind {}", raw_kind))
+        })?;
+        
+        let fd_count = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if fd_count as usize > MAX_FDS_PER_MESSAGE {
+            return Err(ExternalSurfaceError::IpcProtocolError(format!("Too many fds: {}", fd_count)));
+        }
+        
+        let length = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(ExternalSurfaceError::IpcProtocolError(format!("Payload too large: {}", length)));
+        }
+        
+        Ok(Self { version, kind, fd_count, length })
+    }
+}
+
+// A payload type and the message kind it is sent as
+pub trait ProtocolMessage: Serialize + DeserializeOwned {
+    const KIND: MessageKind;
+}
+
+// Features a peer supports, exchanged during the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    // Can wait on binary semaphores received as sync fds and signal imported binary
+    // semaphores (`FrameSyncMode::Binary`)
+    pub const SYNC_FD: Self = Self(1 << 0);
+    // Can import timeline semaphores (`FrameSyncMode::Timeline`)
+    pub const TIMELINE_SEMAPHORES: Self = Self(1 << 1);
+    // Can import DMA-BUF memory described by `DmaBufLayout`
+    pub const DMA_BUF: Self = Self(1 << 2);
+    
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Capabilities {
+    type Output = Self;
+    
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+// Consumer -> producer, first message on a connection. No fds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub capabilities: Capabilities,
+    // Shown in the producer's logs
+    pub name: String,
+}
+
+impl ProtocolMessage for Hello {
+    const KIND: MessageKind = MessageKind::Hello;
+}
+
+// Kind of the memory fds sent with `Metadata`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryHandleType {
+    // VK_KHR_external_memory_fd opaque fd, only importable by Vulkan on the same
+    // device and driver
+    OpaqueFd,
+    // Linux DMA-BUF, laid out as described by the buffer's `DmaBufLayout`
+    DmaBuf,
+}
+
+// One memory plane of a DMA-BUF. All planes live in the buffer's single fd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmaBufPlane {
+    pub offset: u64,
+    pub stride: u64,
+}
+
+// What a DMA-BUF importer (EGL, Wayland linux-dmabuf, GStreamer, VA-API) needs
+// besides the fd
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmaBufLayout {
+    // DRM_FORMAT_* fourcc
+    pub fourcc: u32,
+    pub modifier: u64,
+    pub planes: Vec<DmaBufPlane>,
+}
+
+// Tonemapping operator of the producer's camera, mirroring Bevy's `Tonemapping`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TonemappingOperator {
+    #[default]
+    None,
+    Reinhard,
+    ReinhardLuminance,
+    AcesFitted,
+    AgX,
+    SomewhatBoringDisplayTransform,
+    TonyMcMapface,
+    BlenderFilmic,
+}
+
+// Projection of the producer's camera. Bevy uses reverse Z: depth 1.0 is at the
+// near plane and 0.0 at the far one, which perspective projections put at infinity.
+// Custom projections leave near at 0.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ProjectionInfo {
+    pub near: f32,
+    pub far: f32,
+    // Column major
+    pub clip_from_view: [f32; 16],
+}
+
+// Producer -> consumer, accepts the connection. Carries buffer_count memory fds in
+// buffer order, then as many depth memory fds if the producer shares depth, followed
+// in binary mode by the consumer's own consumer_ready semaphore of every buffer as
+// opaque fds, and in timeline mode by the render_finished timeline and the
+// consumer's own consumer_ready timeline.
+//
+// Sent again with the same consumer_id after a resize, then only carrying the new
+// memory fds. Buffer count, formats and sync mode never change. The camera state is
+// the one at the time it was sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub width: u32,
+    pub height: u32,
+    // Raw VkFormat of the shared images
+    pub format: u32,
+    pub buffer_count: u32,
+    pub sync_mode: FrameSyncMode,
+    pub memory_handle_type: MemoryHandleType,
+    // One per buffer for `MemoryHandleType::DmaBuf`, empty otherwise
+    pub dma_buf_layouts: Vec<DmaBufLayout>,
+    pub color_space: ColorSpace,
+    // Whether the camera rendered in HDR before tonemapping into the shared image
+    pub camera_hdr: bool,
+    pub tonemapping: TonemappingOperator,
+    pub projection: ProjectionInfo,
+    // Raw VkFormat of the depth images, if shared
+    pub depth_format: Option<u32>,
+    pub consumer_id: u64,
+}
+
+impl ProtocolMessage for Metadata {
+    const KIND: MessageKind = MessageKind::Metadata;
+}
+
+// Producer -> consumer, refuses the connection, which is closed afterwards. No fds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reject {
+    pub reason: String,
+}
+
+impl ProtocolMessage for Reject {
+    const KIND: MessageKind = MessageKind::Reject;
+}
+
+// Producer -> consumer, a frame has been submitted into buffer_index.
+//
+// In binary mode it carries a sync fd of the frame's render_finished signal, to
+// import temporarily and wait on before reading the buffer, and frame_value is 0.
+// Once done the consumer signals the buffer's consumer_ready semaphore.
+//
+// In timeline mode it carries no fds: the consumer waits for render_finished to
+// reach frame_value and signals its consumer_ready to frame_value once done.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameReady {
+    pub buffer_index: u32,
+    pub frame_value: u64,
+}
+
+impl ProtocolMessage for FrameReady {
+    const KIND: MessageKind = MessageKind::FrameReady;
+}
+
+// Consumer -> producer, sent after submitting the consumer_ready signal for a
+// frame, which the producer waits on before rendering into the buffer again. No
+// fds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferReleased {
+    pub buffer_index: u32,
+}
+
+impl ProtocolMessage for BufferReleased {
+    const KIND: MessageKind = MessageKind::BufferReleased;
+}
+
+// Consumer -> producer, asks for the shared images to be reallocated at a new size,
+// for example to follow the consumer's viewport. No fds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResizeRequest {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ProtocolMessage for ResizeRequest {
+    const KIND: MessageKind = MessageKind::ResizeRequest;
+}
+
+// A received message. The fds are owned by whoever takes the message.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct Message {
+    pub kind: MessageKind,
+    pub payload: Vec<u8>,
+    pub fds: Vec<RawFd>,
+}
+
+#[cfg(unix)]
+impl Message {
+    pub fn decode<M: ProtocolMessage>(&self) -> Result<M> {
+        if self.kind != M::KIND {
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                format!("Expected {:?}, received {:?}", M::KIND, self.kind),
+            ));
+        }
+        
+        bincode::deserialize(&self.payload)
+            .map_err(|e| ExternalSurfaceError::IpcProtocolError(format!("Failed to decode {:?}: {}", self.kind, e)))
+    }
+    
+    pub fn close_fds(&mut self) {
+        for fd in self.fds.drain(..) {
+            unsafe { libc::close(fd) };
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn encode_message<M: ProtocolMessage>(message: &M, fd_count: usize) -> Result<Vec<u8>> {
+    let payload = bincode::serialize(message)
+        .map_err(|e| ExternalSurfaceError::IpcProtocolError(format!("Failed to encode {:?}: {}", M::KIND, e)))?;
+    
+    let header = MessageHeader {
+        version: PROTOCOL_VERSION,
+        kind: M::KIND,
+        fd_count: fd_count as u16,
+        length: payload.len() as u32,
+    };
+    
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(&header.encode());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+// Sends a message with the fds attached, which stay owned by the caller
+#[cfg(unix)]
+pub fn send_message<M: ProtocolMessage>(socket_fd: RawFd, message: &M, fds: &[RawFd]) -> Result<()> {
+    if fds.len() > MAX_FDS_PER_MESSAGE {
+        return Err(ExternalSurfaceError::IpcProtocolError(format!("Too many fds: {}", fds.len())));
+    }
+    
+    let bytes = encode_message(message, fds.len())?;
+    let mut sent = 0;
+    
+    while sent < bytes.len() {
+        let iov = [std::io::IoSlice::new(&bytes[sent..])];
+        
+        // The fds ride along with the first byte of the message only
+        let result = if sent == 0 && !fds.is_empty() {
+            let cmsg = [socket::ControlMessage::ScmRights(fds)];
+            socket::sendmsg::<()>(socket_fd, &iov, &cmsg, MsgFlags::empty(), None)
+        } else {
+            socket::sendmsg::<()>(socket_fd, &iov, &[], MsgFlags::empty(), None)
+        };
+        
+        // Rust ignores SIGPIPE, so a peer that went away shows up as EPIPE
+        match result {
+            Ok(n) => sent += n,
+            Err(nix::errno::Errno::EINTR) => {}
+            Err(e) if is_disconnect_error(e) => return Err(ExternalSurfaceError::IpcDisconnected),
+            // Only non-blocking sockets get here. Part of the message may have been
+            // sent already, so the stream can't be resumed.
+            Err(nix::errno::Errno::EAGAIN) => {
+                return Err(ExternalSurfaceError::IpcProtocolError(
+                    format!("Sending {:?} would block, the peer stopped reading", M::KIND),
+                ));
+            }
+            Err(e) => {
+                return Err(ExternalSurfaceError::IpcProtocolError(format!("Failed to send {:?}: {}", M::KIND, e)));
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+// Reassembles messages from a stream socket, across partial reads and coalesced
+// writes. Fds received but not yet handed out with a message are closed on drop.
+#[cfg(unix)]
+#[derive(Debug, Default)]
+pub struct MessageReader {
+    bytes: Vec<u8>,
+    fds: VecDeque<RawFd>,
+}
+
+#[cfg(unix)]
+impl MessageReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // Blocks until a whole message has arrived
+    pub fn read_message(&mut self, socket_fd: RawFd) -> Result<Message> {
+        loop {
+            if let Some(message) = self.next_buffered()? {
+                return Ok(message);
+            }
+            self.receive(socket_fd, MsgFlags::empty())?;
+        }
+    }
+    
+    // Returns the next message if it has fully arrived, never blocks
+    pub fn try_read_message(&mut self, socket_fd: RawFd) -> Result<Option<Message>> {
+        loop {
+            if let Some(message) = self.next_buffered()? {
+                return Ok(Some(message));
+            }
+            if !self.receive(socket_fd, MsgFlags::MSG_DONTWAIT)? {
+                return Ok(None);
+            }
+        }
+    }
+    
+    // Receives whatever has arrived without blocking and returns whether a whole
+    // message is buffered, which `read_message` then returns right away
+    pub fn poll_message(&mut self, socket_fd: RawFd) -> Result<bool> {
+        loop {
+            if self.has_buffered()? {
+                return Ok(true);
+            }
+            if !self.receive(socket_fd, MsgFlags::MSG_DONTWAIT)? {
+                return Ok(false);
+            }
+        }
+    }
+    
+    fn has_buffered(&self) -> Result<bool> {
+        let Some(header_bytes) = self.bytes.first_chunk::<HEADER_SIZE>() else {
+            return Ok(false);
+        };
+        
+        let header = MessageHeader::decode(header_bytes)?;
+        Ok(self.bytes.len() >= HEADER_SIZE + header.length as usize)
+    }
+    
+    // Returns false if nothing was available without blocking
+    fn receive(&mut self, socket_fd: RawFd, flags: MsgFlags) -> Result<bool> {
+        let mut buf = [0u8; 4096];
+        let mut cmsg_buf = nix::cmsg_space!([RawFd; MAX_FDS_PER_MESSAGE]);
+        let mut iov = [std::io::IoSliceMut::new(&mut buf)];
+        
+        let received = match socket::recvmsg::<()>(socket_fd, &mut iov, Some(&mut cmsg_buf), flags) {
+            Ok(msg) => {
+                if let Ok(cmsgs) = msg.cmsgs() {
+                    for cmsg in cmsgs {
+                        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+                            self.fds.extend(fds);
+                        }
+                    }
+                }
+                
+                // The kernel closed the fds that did not fit, which no message
+                // sent within the protocol has
+                if msg.flags.contains(MsgFlags::MSG_CTRUNC) {
+                    return Err(ExternalSurfaceError::IpcProtocolError(
+                        format!("More than {} fds in one message", MAX_FDS_PER_MESSAGE),
+                    ));
+                }
+                msg.bytes
+            }
+            Err(nix::errno::Errno::EAGAIN) => return Ok(false),
+            Err(nix::errno::Errno::EINTR) => return Ok(true),
+            Err(e) if is_disconnect_error(e) => return Err(ExternalSurfaceError::IpcDisconnected),
+            Err(e) => {
+                return Err(ExternalSurfaceError::IpcProtocolError(format!("Failed to receive: {}", e)));
+            }
+        };
+        
+        if received == 0 {
+            return Err(ExternalSurfaceError::IpcDisconnected);
+        }
+        
+        self.bytes.extend_from_slice(&buf[..received]);
+        Ok(true)
+    }
+    
+    fn next_buffered(&mut self) -> Result<Option<Message>> {
+        let Some(header_bytes) = self.bytes.first_chunk::<HEADER_SIZE>() else {
+            return Ok(None);
+        };
+        
+        let header = MessageHeader::decode(header_bytes)?;
+        let total = HEADER_SIZE + header.length as usize;
+        if self.bytes.len() < total {
+            return Ok(None);
+        }
+        
+        let fd_count = header.fd_count as usize;
+        if self.fds.len() < fd_count {
+            return Err(ExternalSurfaceError::IpcProtocolError(
+                format!("{:?} announced {} fds, received {}", header.kind, fd_count, self.fds.len()),
+            ));
+        }
+        
+        let payload = self.bytes[HEADER_SIZE..total].to_vec();
+        self.bytes.drain(..total);
+        
+        Ok(Some(Message {
+            kind: header.kind,
+            payload,
+            fds: self.fds.drain(..fd_count).collect(),
+        }))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MessageReader {
+    fn drop(&mut self) {
+        for &fd in &self.fds {
+            unsafe { libc::close(fd) };
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn is_disconnect_error(errno: nix::errno::Errno) -> bool {
+    use nix::errno::Errno;
+    matches!(errno, Errno::EPIPE | Errno::ECONNRESET | Errno::ENOTCONN)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    
+    fn frame(buffer_index: u32, frame_value: u64) -> FrameReady {
+        FrameReady { buffer_index, frame_value }
+    }
+    
+    #[test]
+    fn frames_arrive_in_order() {
+        let (producer, consumer) = UnixStream::pair().unwrap();
+        send_message(producer.as_raw_fd(), &frame(0, 1), &[]).unwrap();
+        send_message(producer.as_raw_fd(), &frame(1, 2), &[]).unwrap();
+        
+        let mut reader = MessageReader::new();
+        for (buffer_index, frame_value) in [(0, 1), (1, 2)] {
+            let message = reader.read_message(consumer.as_raw_fd()).unwrap();
+            let received: FrameReady = message.decode().unwrap();
+            
+            assert_eq!((received.buffer_index, received.frame_value), (buffer_index, frame_value));
+            assert!(message.fds.is_empty());
+        }
+        assert!(reader.try_read_message(consumer.as_raw_fd()).unwrap().is_none());
+    }
+    
+    #[test]
+    fn reads_split_across_the_header() {
+        let (mut producer, consumer) = UnixStream::pair().unwrap();
+        let bytes = encode_message(&BufferReleased { buffer_index: 3 }, 0).unwrap();
+        let mut reader = MessageReader::new();
+        
+        // Part of the header, the rest of it with part of the payload, then the rest
+        for chunk in [&bytes[..6], &bytes[6..HEADER_SIZE + 1]] {
+            producer.write_all(chunk).unwrap();
+            assert!(reader.try_read_message(consumer.as_raw_fd()).unwrap().is_none());
+        }
+        producer.write_all(&bytes[HEADER_SIZE + 1..]).unwrap();
+        
+        let message = reader.try_read_message(consumer.as_raw_fd()).unwrap().unwrap();
+        assert_eq!(message.decode::<BufferReleased>().unwrap().buffer_index, 3);
+    }
+    
+    #[test]
+    fn polling_buffers_the_message_without_taking_it() {
+        let (mut producer, consumer) = UnixStream::pair().unwrap();
+        let bytes = encode_message(&Reject { reason: "busy".into() }, 0).unwrap();
+        let mut reader = MessageReader::new();
+        
+        producer.write_all(&bytes[..HEADER_SIZE]).unwrap();
+        assert!(!reader.poll_message(consumer.as_raw_fd()).unwrap());
+        producer.write_all(&bytes[HEADER_SIZE..]).unwrap();
+        assert!(reader.poll_message(consumer.as_raw_fd()).unwrap());
+        
+        let message = reader.read_message(consumer.as_raw_fd()).unwrap();
+        assert_eq!(message.decode::<Reject>().unwrap().reason, "busy");
+    }
+    
+    #[test]
+    fn fds_travel_with_their_message() {
+        let (producer, consumer) = UnixStream::pair().unwrap();
+        let (shared, _other) = UnixStream::pair().unwrap();
+        send_message(producer.as_raw_fd(), &frame(0, 1), &[shared.as_raw_fd()]).unwrap();
+        send_message(producer.as_raw_fd(), &frame(1, 2), &[]).unwrap();
+        
+        let mut reader = MessageReader::new();
+        let mut first = reader.read_message(consumer.as_raw_fd()).unwrap();
+        let second = reader.read_message(consumer.as_raw_fd()).unwrap();
+        
+        assert_eq!(first.fds.len(), 1);
+        assert!(second.fds.is_empty());
+        first.close_fds();
+    }
+    
+    #[test]
+    fn truncated_fds_are_rejected() {
+        let (producer, consumer) = UnixStream::pair().unwrap();
+        let (shared, _other) = UnixStream::pair().unwrap();
+        
+        // One fd more than the header announces and `send_message` allows, sent by hand
+        let fds = vec![shared.as_raw_fd(); MAX_FDS_PER_MESSAGE + 1];
+        let bytes = encode_message(&frame(0, 1), MAX_FDS_PER_MESSAGE).unwrap();
+        let cmsg = [socket::ControlMessage::ScmRights(&fds)];
+        let iov = [std::io::IoSlice::new(&bytes)];
+        socket::sendmsg::<()>(producer.as_raw_fd(), &iov, &cmsg, MsgFlags::empty(), None).unwrap();
+        
+        let result = MessageReader::new().read_message(consumer.as_raw_fd());
+        assert!(matches!(result, Err(ExternalSurfaceError::IpcProtocolError(e)) if e.contains("fds in one message")));
+    }
+    
+    #[test]
+    fn worst_case_metadata_fits() {
+        let (producer, consumer) = UnixStream::pair().unwrap();
+        let (shared, _other) = UnixStream::pair().unwrap();
+        
+        let layout = DmaBufLayout {
+            fourcc: u32::from_le_bytes(*b"AB4H"),
+            modifier: u64::MAX,
+            planes: vec![DmaBufPlane { offset: u64::MAX, stride: u64::MAX }; 4],
+        };
+        let metadata = Metadata {
+            width: u32::MAX,
+            height: u32::MAX,
+            format: u32::MAX,
+            buffer_count: MAX_BUFFER_COUNT,
+            sync_mode: FrameSyncMode::Binary,
+            memory_handle_type: MemoryHandleType::DmaBuf,
+            dma_buf_layouts: vec![layout; MAX_BUFFER_COUNT as usize],
+            color_space: ColorSpace::ScRgb,
+            camera_hdr: true,
+            tonemapping: TonemappingOperator::SomewhatBoringDisplayTransform,
+            projection: ProjectionInfo::default(),
+            depth_format: Some(u32::MAX),
+            consumer_id: u64::MAX,
+        };
+        
+        // Color and depth memory per buffer, then the consumer_ready semaphores
+        let fds = vec![shared.as_raw_fd(); 3 * MAX_BUFFER_COUNT as usize];
+        send_message(producer.as_raw_fd(), &metadata, &fds).unwrap();
+        
+        let mut message = MessageReader::new().read_message(consumer.as_raw_fd()).unwrap();
+        assert_eq!(message.fds.len(), fds.len());
+        assert_eq!(message.decode::<Metadata>().unwrap().dma_buf_layouts.len(), MAX_BUFFER_COUNT as usize);
+        message.close_fds();
+    }
+    
+    #[test]
+    fn bad_magic_and_version_are_rejected() {
+        let header = MessageHeader {
+            version: PROTOCOL_VERSION,
+            kind: MessageKind::FrameReady,
+            fd_count: 0,
+            length: 0,
+        };
+        assert_eq!(MessageHeader::decode(&header.encode()).unwrap(), header);
+        
+        let mut bad_magic = header.encode();
+        bad_magic[0] = b'X';
+        assert!(MessageHeader::decode(&bad_magic).is_err());
+        
+        let bad_version = MessageHeader { version: PROTOCOL_VERSION + 1, ..header }.encode();
+        assert!(MessageHeader::decode(&bad_version).is_err());
+        
+        // A reader fails on the header rather than waiting for the payload
+        let (mut producer, consumer) = UnixStream::pair().unwrap();
+        producer.write_all(&bad_version).unwrap();
+        assert!(MessageReader::new().try_read_message(consumer.as_raw_fd()).is_err());
+    }
+}